@@ -0,0 +1,253 @@
+use std::{ffi::OsString, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ArgsError {
+    #[error("could not read response file {}: {}", .0.display(), .1)]
+    ResponseFileIo(PathBuf, std::io::Error),
+}
+
+/// Replaces every `@<path>` argument with the arguments read from that
+/// response file.
+///
+/// The first argument is the program name and is never expanded.
+pub fn expand_response_files<I, T>(args: I) -> Result<Vec<OsString>, ArgsError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let mut expanded = Vec::new();
+    for (i, arg) in args.into_iter().enumerate() {
+        let arg = arg.into();
+        let path = match arg.to_str().and_then(|s| s.strip_prefix('@')) {
+            Some(path) if i > 0 && !path.is_empty() => PathBuf::from(path),
+            _ => {
+                expanded.push(arg);
+                continue;
+            }
+        };
+
+        let contents =
+            std::fs::read_to_string(&path).map_err(|e| ArgsError::ResponseFileIo(path, e))?;
+        expanded.extend(
+            parse_response_file(&contents)
+                .into_iter()
+                .map(OsString::from),
+        );
+    }
+
+    Ok(expanded)
+}
+
+/// Appends the flags in `CARGO_ENCODED_RUSTFLAGS` to an `hf rustc`
+/// invocation.
+///
+/// Cargo passes its configured rustflags on the command line and only sets
+/// `CARGO_ENCODED_RUSTFLAGS` for build scripts, so the variable reaches
+/// `hf rustc` when a build script or a wrapper started it from such an
+/// environment. When the command line already carries the flags, e.g.
+/// because Cargo passed them as well, they are not added a second time.
+///
+/// The flags are separated by the ASCII unit separator (0x1f) so they can
+/// contain spaces. Other subcommands are left untouched.
+pub fn append_encoded_rustflags(args: &mut Vec<OsString>, encoded: Option<&str>) {
+    let Some(encoded) = encoded.filter(|encoded| !encoded.is_empty()) else {
+        return;
+    };
+    if args.get(1).and_then(|arg| arg.to_str()) != Some("rustc") {
+        return;
+    }
+
+    let flags = encoded
+        .split('\x1f')
+        .map(OsString::from)
+        .collect::<Vec<_>>();
+    if args[2..].windows(flags.len()).any(|window| window == flags) {
+        return;
+    }
+    args.extend(flags);
+}
+
+/// Splits the contents of a response file into arguments.
+///
+/// Follows the conventions of MSBuild-generated response files: LF or CRLF
+/// line endings, an optional byte order mark, lines starting with `//` are
+/// comments, and arguments are quoted and escaped following the MSVC rules
+/// so Windows paths keep their backslashes.
+pub fn parse_response_file(contents: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    for line in contents.trim_start_matches('\u{feff}').lines() {
+        if line.trim_start().starts_with("//") {
+            continue;
+        }
+        split_line(line, &mut args);
+    }
+    args
+}
+
+fn split_line(line: &str, args: &mut Vec<String>) {
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut backslashes = 1;
+                while chars.peek() == Some(&'\\') {
+                    chars.next();
+                    backslashes += 1;
+                }
+
+                // Backslashes are only escapes when they precede a quote
+                if chars.peek() == Some(&'"') {
+                    current.push_str(&"\\".repeat(backslashes / 2));
+                    if backslashes % 2 == 1 {
+                        chars.next();
+                        current.push('"');
+                    }
+                } else {
+                    current.push_str(&"\\".repeat(backslashes));
+                }
+                in_arg = true;
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response_file() {
+        let contents = "\u{feff}// Generated by MSBuild\r\n\
+                        compile --opt 2\r\n\
+                        \"C:\\Program Files\\HF\\main.hf\"\r\n  \
+                        // indented comment\r\n\
+                        src\\lib.hf \"\"\r\n";
+        assert_eq!(
+            parse_response_file(contents),
+            vec![
+                "compile",
+                "--opt",
+                "2",
+                "C:\\Program Files\\HF\\main.hf",
+                "src\\lib.hf",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_file_escapes() {
+        let cases = vec![
+            (r#"a\"b"#, vec![r#"a"b"#]),
+            (r#""C:\out dir\\""#, vec![r"C:\out dir\"]),
+            (r"\\server\share\a.hf", vec![r"\\server\share\a.hf"]),
+            (r#"--define="A B" c"#, vec!["--define=A B", "c"]),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(parse_response_file(line), expected, "line: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_expand_response_files() {
+        let path = std::env::temp_dir().join("hf-cli-test-expand.rsp");
+        std::fs::write(&path, "--opt 3\r\nmain.hf\r\n").unwrap();
+
+        let args = vec![
+            OsString::from("hf"),
+            OsString::from("compile"),
+            OsString::from(format!("@{}", path.display())),
+        ];
+        let expanded = expand_response_files(args).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(expanded, vec!["hf", "compile", "--opt", "3", "main.hf"]);
+    }
+
+    #[test]
+    fn test_append_encoded_rustflags() {
+        let mut args = vec![
+            OsString::from("hf"),
+            OsString::from("rustc"),
+            OsString::from("main.rs"),
+        ];
+        append_encoded_rustflags(
+            &mut args,
+            Some("-C\x1fopt-level=2\x1f--cfg=feature=\"a b\""),
+        );
+        assert_eq!(
+            args,
+            vec![
+                "hf",
+                "rustc",
+                "main.rs",
+                "-C",
+                "opt-level=2",
+                "--cfg=feature=\"a b\""
+            ]
+        );
+
+        let mut args = vec![
+            OsString::from("hf"),
+            OsString::from("rustc"),
+            OsString::from("-C"),
+            OsString::from("opt-level=2"),
+            OsString::from("main.rs"),
+        ];
+        append_encoded_rustflags(&mut args, Some("-C\x1fopt-level=2"));
+        assert_eq!(args, vec!["hf", "rustc", "-C", "opt-level=2", "main.rs"]);
+
+        let mut args = vec![OsString::from("hf"), OsString::from("rustc")];
+        append_encoded_rustflags(&mut args, Some(""));
+        assert_eq!(args, vec!["hf", "rustc"]);
+
+        let mut args = vec![OsString::from("hf"), OsString::from("compile")];
+        append_encoded_rustflags(&mut args, Some("-O"));
+        assert_eq!(args, vec!["hf", "compile"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_expand_response_files_windows_paths() {
+        let dir = std::env::temp_dir().join("hf cli test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("args.rsp");
+        std::fs::write(
+            &path,
+            "// MSBuild response file\r\n\"C:\\Users\\Build Agent\\src\\main.hf\"\r\n",
+        )
+        .unwrap();
+
+        // The temp dir is a backslash separated path on Windows
+        let arg = format!("@{}", path.display());
+        assert!(arg.contains('\\'));
+        let expanded = expand_response_files(vec!["hf".to_string(), arg]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(expanded, vec!["hf", r"C:\Users\Build Agent\src\main.hf"]);
+    }
+}
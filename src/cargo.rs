@@ -0,0 +1,97 @@
+use std::{ffi::OsString, path::Path};
+
+use crate::{
+    process::{self, ExitOutcome},
+    stream::StreamPolicy,
+};
+
+/// Runs Cargo with every compiler invocation routed through `hf rustc`.
+///
+/// Cargo runs `$RUSTC_WRAPPER $RUSTC <args>`, so pointing the wrapper at this
+/// binary and `RUSTC` at `rustc` makes Cargo invoke `hf rustc <args>`.
+///
+/// `hf rustc` answers Cargo's `-vV` and target probe, but then compiles
+/// each crate's Rust sources as HF and emits only object files, so building
+/// a Rust package fails at its first crate.
+pub fn cargo(args: &[String], streams: &mut StreamPolicy) -> i32 {
+    let Some(hf) = process::hf_binary(streams) else {
+        return 1;
+    };
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    for warning in replaced_compiler_vars(&hf, |name| std::env::var_os(name)) {
+        writeln!(streams.human(), "warning: {}", warning).ok();
+    }
+
+    // Cargo writes its own payloads (e.g. `cargo metadata`) to our stdout
+    streams.claim_stdout();
+    let outcome = ExitOutcome::wait(
+        std::process::Command::new(&cargo)
+            .args(args)
+            .env("RUSTC_WRAPPER", hf)
+            .env("RUSTC", "rustc"),
+    );
+
+    // Cargo reports its own failures, only say something when it did not
+    // get the chance to
+    if !matches!(outcome, ExitOutcome::Exited(_)) {
+        writeln!(
+            streams.human(),
+            "error: {}",
+            outcome.describe(&cargo.to_string_lossy())
+        )
+        .ok();
+    }
+    outcome.exit_code()
+}
+
+/// Describes the user's `RUSTC_WRAPPER` and `RUSTC` settings that running
+/// Cargo through hf replaces.
+///
+/// Cargo only runs a single wrapper and `hf rustc` does not pass its
+/// invocations on, so wrappers such as sccache are bypassed entirely.
+fn replaced_compiler_vars(hf: &Path, var: impl Fn(&str) -> Option<OsString>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let Some(wrapper) = var("RUSTC_WRAPPER").filter(|w| !w.is_empty() && Path::new(w) != hf) {
+        warnings.push(format!(
+            "ignoring RUSTC_WRAPPER={}, hf replaces it for this build",
+            wrapper.to_string_lossy()
+        ));
+    }
+    if let Some(rustc) = var("RUSTC").filter(|r| !r.is_empty() && r != "rustc") {
+        warnings.push(format!(
+            "ignoring RUSTC={}, hf replaces it for this build",
+            rustc.to_string_lossy()
+        ));
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replaced_compiler_vars() {
+        let hf = Path::new("/opt/hf/bin/hf");
+        let replaced = |vars: &[(&str, &str)]| {
+            replaced_compiler_vars(hf, |name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            })
+        };
+
+        assert!(replaced(&[]).is_empty());
+        assert!(replaced(&[("RUSTC_WRAPPER", "/opt/hf/bin/hf"), ("RUSTC", "rustc")]).is_empty());
+        assert_eq!(
+            replaced(&[
+                ("RUSTC_WRAPPER", "sccache"),
+                ("RUSTC", "/usr/bin/rustc-1.80")
+            ]),
+            vec![
+                "ignoring RUSTC_WRAPPER=sccache, hf replaces it for this build",
+                "ignoring RUSTC=/usr/bin/rustc-1.80, hf replaces it for this build",
+            ]
+        );
+    }
+}
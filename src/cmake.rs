@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+
+use crate::{process, stream::StreamPolicy, TargetTriplet};
+
+/// Writes a CMake toolchain file pointing CMake's compilers at hf.
+pub fn cmake(output: PathBuf, target: Option<TargetTriplet>, streams: &mut StreamPolicy) -> i32 {
+    let Some(hf) = process::hf_binary(streams) else {
+        return 1;
+    };
+
+    let contents = toolchain_file(&hf, target.as_ref());
+    if let Err(e) = std::fs::write(&output, contents) {
+        writeln!(
+            streams.human(),
+            "error: could not write {}: {}",
+            output.display(),
+            e
+        )
+        .ok();
+        return 1;
+    }
+
+    writeln!(
+        streams.human(),
+        "Wrote CMake toolchain file {}!",
+        output.display()
+    )
+    .ok();
+    0
+}
+
+fn toolchain_file(hf: &Path, target: Option<&TargetTriplet>) -> String {
+    // The install prefix is the directory above `bin/`
+    let bin_dir = hf.parent().unwrap_or(Path::new("."));
+    let root = bin_dir.parent().unwrap_or(bin_dir);
+
+    let mut contents = String::from("# Generated by `hf cmake`.\n");
+    if let Some(target) = target {
+        contents.push_str(&format!(
+            "set(CMAKE_SYSTEM_NAME {})\nset(CMAKE_SYSTEM_PROCESSOR {})\n\n",
+            system_name(&target.system),
+            processor(target),
+        ));
+    }
+    contents.push_str(&format!(
+        "set(CMAKE_C_COMPILER \"{hf}\")\n\
+         set(CMAKE_ASM_COMPILER \"{hf}\")\n\
+         \n\
+         set(CMAKE_FIND_ROOT_PATH \"{root}\")\n",
+        hf = cmake_path(hf),
+        root = cmake_path(root),
+    ));
+    // Restricting the search to hf's prefix only makes sense for a foreign
+    // system, host builds still need the system libraries and headers
+    if target.is_some() {
+        contents.push_str(
+            "set(CMAKE_FIND_ROOT_PATH_MODE_PROGRAM NEVER)\n\
+             set(CMAKE_FIND_ROOT_PATH_MODE_LIBRARY ONLY)\n\
+             set(CMAKE_FIND_ROOT_PATH_MODE_INCLUDE ONLY)\n",
+        );
+    }
+    contents
+}
+
+/// CMake expects forward slashes, even on Windows.
+fn cmake_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn system_name(system: &str) -> &'static str {
+    match system {
+        "linux" => "Linux",
+        "windows" => "Windows",
+        "bsd" => "FreeBSD",
+        "macos" => "Darwin",
+        "ios" => "iOS",
+        "android" => "Android",
+        "solaris" | "illumos" => "SunOS",
+        "haiku" => "Haiku",
+        _ => "Generic",
+    }
+}
+
+/// CMake takes any processor name, so architectures without a GNU name
+/// keep hf's.
+fn processor(target: &TargetTriplet) -> &'static str {
+    let arch = target.target.arch;
+    crate::arch_aliases(arch).map_or(crate::arch_name(arch), |aliases| aliases.gnu)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_toolchain_file() {
+        let target = TargetTriplet::from_str("x86-unknown-linux").unwrap();
+        let contents = toolchain_file(Path::new("/opt/hf/bin/hf"), Some(&target));
+
+        assert!(contents.contains("set(CMAKE_SYSTEM_NAME Linux)\n"));
+        assert!(contents.contains("set(CMAKE_SYSTEM_PROCESSOR i686)\n"));
+        assert!(contents.contains("set(CMAKE_C_COMPILER \"/opt/hf/bin/hf\")\n"));
+        assert!(contents.contains("set(CMAKE_ASM_COMPILER \"/opt/hf/bin/hf\")\n"));
+        assert!(contents.contains("set(CMAKE_FIND_ROOT_PATH \"/opt/hf\")\n"));
+        assert!(contents.contains("set(CMAKE_FIND_ROOT_PATH_MODE_LIBRARY ONLY)\n"));
+
+        let target = TargetTriplet::from_str("z390-unknown-linux").unwrap();
+        assert_eq!(processor(&target), "s390x");
+    }
+
+    #[test]
+    fn test_toolchain_file_host() {
+        let contents = toolchain_file(Path::new("/opt/hf/bin/hf"), None);
+
+        assert!(!contents.contains("CMAKE_SYSTEM_NAME"));
+        assert!(!contents.contains("CMAKE_FIND_ROOT_PATH_MODE"));
+        assert!(contents.contains("set(CMAKE_C_COMPILER \"/opt/hf/bin/hf\")\n"));
+    }
+}
@@ -6,6 +6,11 @@ use hf_parser_rust::{ast::SyntaxError, token::TokenizerError};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::{
+    error_format::{self, Diagnostic, ErrorFormat, Severity},
+    stream::StreamPolicy,
+};
+
 #[derive(Debug, Error)]
 pub enum CompilationError {
     #[error("io error: {0}")]
@@ -19,18 +24,78 @@ pub enum CompilationError {
 
     #[error("compiler error: {0:?}")]
     CompilerError(CompilerError),
+
+    #[error("source removed during build")]
+    SourceVanished,
+}
+
+/// A source file and whether it existed when the build started.
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    /// Whether the file existed when it was discovered.
+    pub discovered: bool,
+}
+
+impl SourceFile {
+    pub fn discover(path: PathBuf) -> Self {
+        let discovered = path.exists();
+        SourceFile { path, discovered }
+    }
 }
 
 impl CompilationError {
-    pub fn pretty_print(&self, path: &Path, code: &str) {
+    /// Reports this error in the given format.
+    pub fn report(&self, path: &Path, code: &str, format: ErrorFormat, streams: &mut StreamPolicy) {
+        let diagnostic = self.diagnostic(path);
+        let line = match format {
+            ErrorFormat::Human => return self.pretty_print(path, code, streams),
+            ErrorFormat::Github => error_format::github(&diagnostic),
+            ErrorFormat::Teamcity => error_format::teamcity(&diagnostic),
+            ErrorFormat::Azuredevops => error_format::azuredevops(&diagnostic),
+        };
+        writeln!(streams.payload(), "{}", line).ok();
+    }
+
+    pub fn diagnostic<'a>(&self, path: &'a Path) -> Diagnostic<'a> {
+        let severity = match self {
+            Self::SourceVanished => Severity::Notice,
+            _ => Severity::Error,
+        };
+        let location = match self {
+            Self::TokenizerError(e) => Some(e.location),
+            Self::AstBuilderError(e) => Some(e.location),
+            _ => None,
+        };
+
+        Diagnostic {
+            severity,
+            path,
+            location,
+            message: self.to_string(),
+        }
+    }
+
+    pub fn pretty_print(&self, path: &Path, code: &str, streams: &mut StreamPolicy) {
+        let out = streams.human();
+
         // TODO: Handle these cases nicer
         match self {
             Self::IoError(e) => {
-                eprintln!("IO error: {}", e);
+                writeln!(out, "IO error: {}", e).ok();
                 return;
             }
             Self::CompilerError(e) => {
-                eprintln!("Compiler error: {:?}", e);
+                writeln!(out, "Compiler error: {:?}", e).ok();
+                return;
+            }
+            Self::SourceVanished => {
+                writeln!(
+                    out,
+                    "notice: {} was removed during the build",
+                    path.display()
+                )
+                .ok();
                 return;
             }
             _ => {}
@@ -74,52 +139,70 @@ impl CompilationError {
             .map(|(i, s)| (i, s.to_string()))
             .collect::<Vec<_>>();
 
-        eprintln!("error: {}", err_fmt);
-        eprintln!(
+        writeln!(out, "error: {}", err_fmt).ok();
+        writeln!(
+            out,
             "-> {}:{}:{}",
             path.display(),
             location.0 + 1,
             location.1 + 1
-        );
+        )
+        .ok();
         for (i, line) in relevant_lines {
-            eprintln!("{:4} | {}", i + 1, line,);
+            writeln!(out, "{:4} | {}", i + 1, line,).ok();
             if i == underline_line {
                 let underline = (0..location.1)
                     .map(|_| ' ')
                     .chain("^".repeat(underline_len).chars())
                     .collect::<String>();
-                eprintln!("     | {}", underline);
+                writeln!(out, "     | {}", underline).ok();
             }
         }
     }
 }
 
 pub fn compile(
-    path: PathBuf,
+    source: SourceFile,
     target: Target,
     settings: &CompilerSettings,
+    format: ErrorFormat,
+    streams: &mut StreamPolicy,
 ) -> Result<(), CompilationError> {
-    let code = std::fs::read_to_string(&path).map_err(|e| CompilationError::IoError(e))?;
+    let SourceFile { path, discovered } = source;
+    let code = match std::fs::read_to_string(&path) {
+        Ok(code) => code,
+        Err(e) => {
+            // A file that existed when the build started was removed
+            // concurrently, which is not a problem with the source itself
+            let e = if e.kind() == std::io::ErrorKind::NotFound && discovered {
+                CompilationError::SourceVanished
+            } else {
+                CompilationError::IoError(e)
+            };
+            e.report(path.as_path(), "", format, streams);
+            return Err(e);
+        }
+    };
     let tokens = match hf_parser_rust::token::tokenize(&code) {
         Ok(tokens) => {
-            println!("Tokens:\n{:#?}\n", tokens);
+            writeln!(streams.human(), "Tokens:\n{:#?}\n", tokens).ok();
             tokens
         }
         Err(e) => {
             let e = CompilationError::TokenizerError(e);
-            e.pretty_print(path.as_path(), &code);
+            e.report(path.as_path(), &code, format, streams);
             return Err(e);
         }
     };
 
     let ast = match hf_parser_rust::ast::build_ast(tokens) {
         Ok(ast) => {
-            println!("Ast:\n{:#?}\n", ast);
+            writeln!(streams.human(), "Ast:\n{:#?}\n", ast).ok();
             ast
         }
         Err(e) => {
             let e = CompilationError::AstBuilderError(e);
-            e.pretty_print(path.as_path(), &code);
+            e.report(path.as_path(), &code, format, streams);
             return Err(e);
         }
     };
@@ -127,20 +210,25 @@ pub fn compile(
     let ir = hf_codegen::ir::from_ast(ast);
 
     let mut compiler = hf_codegen::compiler::HfCompiler::new(target, settings.clone());
-    let obj = compiler
-        .compile_to_object_file(
-            ir,
-            path.file_name()
-                .map(|s| s.to_string_lossy().to_string())
-                .unwrap_or(String::new())
-                .as_str(),
-        )
-        .map_err(|e| CompilationError::CompilerError(e))?;
+    let obj = match compiler.compile_to_object_file(
+        ir,
+        path.file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or(String::new())
+            .as_str(),
+    ) {
+        Ok(obj) => obj,
+        Err(e) => {
+            let e = CompilationError::CompilerError(e);
+            e.report(path.as_path(), &code, format, streams);
+            return Err(e);
+        }
+    };
 
     let raw = obj.write().expect("Failed to write object file to buffer!");
     let obj_path = path.with_extension("o");
     std::fs::write(&obj_path, raw).expect("Failed to write object file!");
-    println!("Wrote object file {}!", obj_path.display());
+    writeln!(streams.human(), "Wrote object file {}!", obj_path.display()).ok();
 
     Ok(())
 }
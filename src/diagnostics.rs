@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use crate::{
+    stream::StreamPolicy,
+    targets::{self, Failure, HostEnvironment},
+    TargetTriplet,
+};
+
+/// The outcome of one environment check.
+#[derive(Debug)]
+struct Check {
+    name: &'static str,
+    result: Result<String, Failure>,
+}
+
+/// Checks the parts of this machine hf depends on and prints the results.
+///
+/// Returns `0` if every check passed, `1` otherwise.
+pub fn diagnostics(streams: &mut StreamPolicy) -> i32 {
+    let host = TargetTriplet::host();
+    let checks = vec![
+        Check {
+            name: "hf",
+            result: check_installation(),
+        },
+        Check {
+            name: "target",
+            result: check_target(&host),
+        },
+        Check {
+            name: "linker",
+            result: targets::probe_link(&host, &HostEnvironment),
+        },
+        Check {
+            name: "temp dir",
+            result: check_writable(
+                &std::env::temp_dir(),
+                "set TMPDIR (TEMP on Windows) to a writable directory",
+            ),
+        },
+        // Object files are written next to their sources
+        Check {
+            name: "work dir",
+            result: std::env::current_dir()
+                .map_err(|e| Failure {
+                    detail: format!("cannot determine the current directory: {}", e),
+                    hint: "run hf from an existing directory".to_string(),
+                })
+                .and_then(|dir| check_writable(&dir, "build from a directory you can write to")),
+        },
+    ];
+
+    streams.claim_stdout();
+    write!(streams.payload(), "{}", format(&checks)).ok();
+
+    if checks.iter().any(|check| check.result.is_err()) {
+        1
+    } else {
+        0
+    }
+}
+
+fn check_installation() -> Result<String, Failure> {
+    let version = env!("CARGO_PKG_VERSION");
+    match std::env::current_exe() {
+        Ok(path) => Ok(format!("version {} at {}", version, path.display())),
+        Err(e) => Err(Failure {
+            detail: format!(
+                "version {}, could not locate the executable: {}",
+                version, e
+            ),
+            hint: "run hf by its full path".to_string(),
+        }),
+    }
+}
+
+fn check_target(host: &TargetTriplet) -> Result<String, Failure> {
+    targets::compile_smoke_program(host)
+        .map(|_| format!("{}, code generation works", host))
+        .map_err(|detail| Failure {
+            detail: format!("{}: {}", host, detail),
+            hint: "pass --target to compile for a supported target".to_string(),
+        })
+}
+
+fn check_writable(dir: &Path, hint: &str) -> Result<String, Failure> {
+    let probe = dir.join(format!("hf-diagnostics-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            std::fs::remove_file(&probe).ok();
+            Ok(format!("{} is writable", dir.display()))
+        }
+        Err(e) => Err(Failure {
+            detail: format!("cannot write to {}: {}", dir.display(), e),
+            hint: hint.to_string(),
+        }),
+    }
+}
+
+fn format(checks: &[Check]) -> String {
+    let mut out = String::new();
+    for check in checks {
+        match &check.result {
+            Ok(detail) => out.push_str(&format!("[OK]   {:<8} {}\n", check.name, detail)),
+            Err(failure) => {
+                out.push_str(&format!("[FAIL] {:<8} {}\n", check.name, failure.detail));
+                out.push_str(&format!("       {:<8} fix: {}\n", "", failure.hint));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_writable() {
+        let dir = std::env::temp_dir();
+        assert!(check_writable(&dir, "").is_ok());
+
+        let missing = dir.join("hf-diagnostics-missing").join("nested");
+        let failure = check_writable(&missing, "create it").unwrap_err();
+        assert!(failure.detail.starts_with("cannot write to "));
+        assert_eq!(failure.hint, "create it");
+    }
+
+    #[test]
+    fn test_format() {
+        let checks = [
+            Check {
+                name: "hf",
+                result: Ok("version 0.1.0 at /usr/bin/hf".to_string()),
+            },
+            Check {
+                name: "linker",
+                result: Err(Failure {
+                    detail: "not found (looked for cc)".to_string(),
+                    hint: "install a C toolchain".to_string(),
+                }),
+            },
+        ];
+        assert_eq!(
+            format(&checks).lines().collect::<Vec<_>>(),
+            vec![
+                "[OK]   hf       version 0.1.0 at /usr/bin/hf",
+                "[FAIL] linker   not found (looked for cc)",
+                "                fix: install a C toolchain",
+            ]
+        );
+    }
+}
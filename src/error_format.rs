@@ -0,0 +1,414 @@
+use clap::ValueEnum;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::json;
+
+/// Where GitLab's Code Quality widget conventionally picks up reports.
+const GITLAB_CODEQUALITY_REPORT: &str = "gl-code-quality-report.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Source snippets with the error location underlined.
+    Human,
+    /// GitHub Actions workflow commands, shown as inline annotations on pull requests.
+    Github,
+    /// TeamCity service messages, marking the build as failed on errors.
+    Teamcity,
+    /// Azure Pipelines logging commands, shown as issues in the pipeline summary.
+    Azuredevops,
+}
+
+impl ErrorFormat {
+    /// Picks the format matching the CI system that set the environment
+    /// variables `var` looks up.
+    pub fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        if var("GITHUB_ACTIONS").is_some_and(|value| value == "true") {
+            ErrorFormat::Github
+        } else if var("TEAMCITY_VERSION").is_some() {
+            ErrorFormat::Teamcity
+        } else if var("TF_BUILD").is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+            ErrorFormat::Azuredevops
+        } else {
+            ErrorFormat::Human
+        }
+    }
+
+    /// Whether diagnostics in this format are payloads written to stdout.
+    pub fn is_machine_readable(self) -> bool {
+        self != ErrorFormat::Human
+    }
+}
+
+/// Returns the default Code Quality report path when running under GitLab CI.
+pub fn detect_gitlab_codequality_report(var: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    var("GITLAB_CI").map(|_| PathBuf::from(GITLAB_CODEQUALITY_REPORT))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Notice,
+}
+
+/// The parts of a diagnostic the machine-readable formats report.
+#[derive(Debug)]
+pub struct Diagnostic<'a> {
+    pub severity: Severity,
+    pub path: &'a Path,
+    /// The zero-based line and column the diagnostic points at.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+/// Formats a diagnostic as a GitHub Actions workflow command, e.g.
+/// `::error file=src/main.hf,line=3,col=5::unexpected token`.
+pub fn github(diagnostic: &Diagnostic) -> String {
+    let command = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Notice => "notice",
+    };
+
+    let mut properties = format!(
+        "file={}",
+        github_escape_property(&diagnostic.path.to_string_lossy())
+    );
+    if let Some((line, column)) = diagnostic.location {
+        properties.push_str(&format!(",line={},col={}", line + 1, column + 1));
+    }
+
+    format!(
+        "::{} {}::{}",
+        command,
+        properties,
+        github_escape_data(&diagnostic.message)
+    )
+}
+
+/// Formats a diagnostic as an issue in a GitLab Code Quality report, which
+/// follows the Code Climate issue schema.
+pub fn gitlab_issue(diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Severity::Error => "major",
+        Severity::Notice => "info",
+    };
+    let path = diagnostic.path.to_string_lossy().replace('\\', "/");
+    let line = diagnostic.location.map_or(1, |(line, _)| line + 1);
+
+    // GitLab compares fingerprints between pipelines to tell new issues from
+    // fixed ones, so they must be stable across runs and toolchain versions
+    let fingerprint = fnv1a(&format!("{}:{}:{}", path, line, diagnostic.message));
+
+    format!(
+        "{{\"description\":{},\"check_name\":\"hf-compile\",\"fingerprint\":\"{:016x}\",\
+         \"severity\":\"{}\",\"location\":{{\"path\":{},\"lines\":{{\"begin\":{}}}}}}}",
+        json::string(&diagnostic.message),
+        fingerprint,
+        severity,
+        json::string(&path),
+        line
+    )
+}
+
+#[derive(Debug, Error)]
+pub enum ReportError {
+    #[error("the existing report is not a JSON array of issues")]
+    NotAnIssueArray,
+}
+
+/// Formats a GitLab Code Quality report of `issues`, following the issues
+/// of the `existing` report.
+///
+/// Issues already in the report, going by their fingerprint, are not added
+/// again, so retried jobs do not report everything twice.
+pub fn gitlab_report(existing: Option<&str>, issues: &[String]) -> Result<String, ReportError> {
+    let existing = match existing.map(str::trim) {
+        Some(report) => report
+            .strip_prefix('[')
+            .and_then(|report| report.strip_suffix(']'))
+            .map(str::trim)
+            .filter(|issues| {
+                issues.is_empty() || (issues.starts_with('{') && issues.ends_with('}'))
+            })
+            .ok_or(ReportError::NotAnIssueArray)?,
+        None => "",
+    };
+
+    let mut known = fingerprints(existing);
+    let mut report = existing.to_string();
+    for issue in issues {
+        let fingerprint = fingerprints(issue).into_iter().next();
+        if fingerprint.is_some_and(|fingerprint| !known.insert(fingerprint)) {
+            continue;
+        }
+        if !report.is_empty() {
+            report.push(',');
+        }
+        report.push_str(issue);
+    }
+    Ok(format!("[{}]\n", report))
+}
+
+/// Returns the values of every `"fingerprint"` key in `json`.
+fn fingerprints(json: &str) -> HashSet<&str> {
+    json.match_indices("\"fingerprint\"")
+        .filter_map(|(i, key)| {
+            let value = json[i + key.len()..].trim_start().strip_prefix(':')?;
+            let value = value.trim_start().strip_prefix('"')?;
+            value.split('"').next()
+        })
+        .collect()
+}
+
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Formats a diagnostic as a TeamCity service message, e.g.
+/// `##teamcity[buildProblem description='src/main.hf:3:5: unexpected token']`.
+pub fn teamcity(diagnostic: &Diagnostic) -> String {
+    let mut text = diagnostic.path.to_string_lossy().to_string();
+    if let Some((line, column)) = diagnostic.location {
+        text.push_str(&format!(":{}:{}", line + 1, column + 1));
+    }
+    text.push_str(": ");
+    text.push_str(&diagnostic.message);
+
+    match diagnostic.severity {
+        Severity::Error => format!(
+            "##teamcity[buildProblem description='{}']",
+            teamcity_escape(&text)
+        ),
+        Severity::Notice => format!(
+            "##teamcity[message text='{}' status='WARNING']",
+            teamcity_escape(&text)
+        ),
+    }
+}
+
+/// Formats a diagnostic as an Azure Pipelines logging command, e.g.
+/// `##vso[task.logissue type=error;sourcepath=src/main.hf;linenumber=3;columnnumber=5]unexpected token`.
+pub fn azuredevops(diagnostic: &Diagnostic) -> String {
+    let kind = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Notice => "warning",
+    };
+
+    let mut properties = format!(
+        "type={};sourcepath={}",
+        kind,
+        azuredevops_escape_property(&diagnostic.path.to_string_lossy())
+    );
+    if let Some((line, column)) = diagnostic.location {
+        properties.push_str(&format!(
+            ";linenumber={};columnnumber={}",
+            line + 1,
+            column + 1
+        ));
+    }
+
+    format!(
+        "##vso[task.logissue {}]{}",
+        properties,
+        azuredevops_escape_data(&diagnostic.message)
+    )
+}
+
+fn azuredevops_escape_data(s: &str) -> String {
+    s.replace('%', "%AZP25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn azuredevops_escape_property(s: &str) -> String {
+    azuredevops_escape_data(s)
+        .replace(';', "%3B")
+        .replace(']', "%5D")
+}
+
+fn teamcity_escape(s: &str) -> String {
+    s.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+fn github_escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn github_escape_property(s: &str) -> String {
+    github_escape_data(s)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let detect = |vars: &[(&str, &str)]| {
+            ErrorFormat::detect(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+
+        assert_eq!(detect(&[]), ErrorFormat::Human);
+        assert_eq!(detect(&[("GITHUB_ACTIONS", "true")]), ErrorFormat::Github);
+        assert_eq!(detect(&[("GITHUB_ACTIONS", "false")]), ErrorFormat::Human);
+        assert_eq!(
+            detect(&[("TEAMCITY_VERSION", "2024.03")]),
+            ErrorFormat::Teamcity
+        );
+        assert_eq!(detect(&[("TF_BUILD", "True")]), ErrorFormat::Azuredevops);
+    }
+
+    #[test]
+    fn test_github() {
+        let error = Diagnostic {
+            severity: Severity::Error,
+            path: Path::new("src/main.hf"),
+            location: Some((2, 4)),
+            message: "unexpected token\n100% wrong".to_string(),
+        };
+        assert_eq!(
+            github(&error),
+            "::error file=src/main.hf,line=3,col=5::unexpected token%0A100%25 wrong"
+        );
+
+        let notice = Diagnostic {
+            severity: Severity::Notice,
+            path: Path::new("C:\\a,b.hf"),
+            location: None,
+            message: "source removed during build".to_string(),
+        };
+        assert_eq!(
+            github(&notice),
+            "::notice file=C%3A\\a%2Cb.hf::source removed during build"
+        );
+    }
+
+    #[test]
+    fn test_teamcity() {
+        let error = Diagnostic {
+            severity: Severity::Error,
+            path: Path::new("src/main.hf"),
+            location: Some((2, 4)),
+            message: "expected ']', found 'x'\n|".to_string(),
+        };
+        assert_eq!(
+            teamcity(&error),
+            "##teamcity[buildProblem description='src/main.hf:3:5: expected |'|]|', found |'x|'|n||']"
+        );
+
+        let notice = Diagnostic {
+            severity: Severity::Notice,
+            path: Path::new("a.hf"),
+            location: None,
+            message: "source removed during build".to_string(),
+        };
+        assert_eq!(
+            teamcity(&notice),
+            "##teamcity[message text='a.hf: source removed during build' status='WARNING']"
+        );
+    }
+
+    #[test]
+    fn test_azuredevops() {
+        let error = Diagnostic {
+            severity: Severity::Error,
+            path: Path::new("src/a;b].hf"),
+            location: Some((2, 4)),
+            message: "unexpected token\n100% wrong".to_string(),
+        };
+        assert_eq!(
+            azuredevops(&error),
+            "##vso[task.logissue type=error;sourcepath=src/a%3Bb%5D.hf;linenumber=3;columnnumber=5]\
+             unexpected token%0A100%AZP25 wrong"
+        );
+
+        let notice = Diagnostic {
+            severity: Severity::Notice,
+            path: Path::new("a.hf"),
+            location: None,
+            message: "source removed during build".to_string(),
+        };
+        assert_eq!(
+            azuredevops(&notice),
+            "##vso[task.logissue type=warning;sourcepath=a.hf]source removed during build"
+        );
+    }
+
+    #[test]
+    fn test_gitlab_issue() {
+        let error = Diagnostic {
+            severity: Severity::Error,
+            path: Path::new("src\\main.hf"),
+            location: Some((2, 4)),
+            message: "unexpected \"token\"".to_string(),
+        };
+        let issue = gitlab_issue(&error);
+
+        assert!(issue.starts_with(
+            "{\"description\":\"unexpected \\\"token\\\"\",\"check_name\":\"hf-compile\",\"fingerprint\":\""
+        ));
+        assert!(issue.ends_with(
+            "\"severity\":\"major\",\"location\":{\"path\":\"src/main.hf\",\"lines\":{\"begin\":3}}}"
+        ));
+        assert_eq!(issue, gitlab_issue(&error), "fingerprints must be stable");
+    }
+
+    #[test]
+    fn test_gitlab_report() {
+        let issues = ["{\"fingerprint\":\"b\"}".to_string()];
+        assert_eq!(
+            gitlab_report(None, &issues).unwrap(),
+            "[{\"fingerprint\":\"b\"}]\n"
+        );
+        assert_eq!(
+            gitlab_report(Some("[]\n"), &issues).unwrap(),
+            "[{\"fingerprint\":\"b\"}]\n"
+        );
+        assert_eq!(
+            gitlab_report(Some("[\n  {\"fingerprint\": \"a\"}\n]\n"), &issues).unwrap(),
+            "[{\"fingerprint\": \"a\"},{\"fingerprint\":\"b\"}]\n"
+        );
+        assert_eq!(
+            gitlab_report(Some("[{\"fingerprint\":\"a\"}]"), &[]).unwrap(),
+            "[{\"fingerprint\":\"a\"}]\n"
+        );
+        assert!(matches!(
+            gitlab_report(Some("not json"), &issues),
+            Err(ReportError::NotAnIssueArray)
+        ));
+        assert!(matches!(
+            gitlab_report(Some("[1, 2]"), &issues),
+            Err(ReportError::NotAnIssueArray)
+        ));
+
+        // Reporting the same issues again, e.g. from a retried job, adds nothing
+        let report = gitlab_report(None, &issues).unwrap();
+        assert_eq!(gitlab_report(Some(&report), &issues).unwrap(), report);
+        let twice = [issues[0].clone(), issues[0].clone()];
+        assert_eq!(gitlab_report(None, &twice).unwrap(), report);
+    }
+}
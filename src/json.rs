@@ -0,0 +1,38 @@
+/// Formats `s` as a quoted JSON string.
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Formats an optional string as a JSON string or `null`.
+pub fn optional_string(s: Option<&str>) -> String {
+    s.map(string).unwrap_or_else(|| "null".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(string("plain"), "\"plain\"");
+        assert_eq!(
+            string("C:\\src\\\"a\".hf\n\u{1}"),
+            "\"C:\\\\src\\\\\\\"a\\\".hf\\n\\u0001\""
+        );
+        assert_eq!(optional_string(None), "null");
+    }
+}
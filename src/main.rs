@@ -1,5 +1,5 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::{ffi::OsString, path::PathBuf};
 use thiserror::Error;
 
 use hf_codegen::{
@@ -7,7 +7,22 @@ use hf_codegen::{
     target::{Arch, CallingConvention, Os, Target},
 };
 
+mod args;
+mod cargo;
+mod cmake;
 mod compile;
+mod diagnostics;
+mod error_format;
+mod json;
+mod meson;
+mod path_matcher;
+mod print;
+mod process;
+mod rustc;
+mod stream;
+mod targets;
+
+use stream::StreamPolicy;
 
 #[derive(Debug, Error)]
 enum ParseError {
@@ -15,11 +30,146 @@ enum ParseError {
     InvalidTargetTriplet,
     #[error("unknown host in target triplet")]
     UnknownTargetTripletHost,
+    #[error("unknown system in target triplet")]
+    UnknownTargetTripletSystem,
+    #[error("macOS and iOS targets are not supported yet")]
+    UnsupportedTargetTripletSystem,
 }
 
 #[derive(Debug, Clone)]
 struct TargetTriplet {
     target: Target,
+    vendor: String,
+    system: String,
+}
+
+impl TargetTriplet {
+    /// Returns the triplet of the machine running the compiler.
+    ///
+    /// The machine is described directly instead of through `from_str`, so
+    /// systems hf cannot target yet, such as macOS, keep their own name.
+    fn host() -> Self {
+        let system = match std::env::consts::OS {
+            "freebsd" | "openbsd" | "netbsd" | "dragonfly" => "bsd",
+            os => os,
+        };
+
+        TargetTriplet {
+            target: Target::native(),
+            vendor: "unknown".to_string(),
+            system: system.to_string(),
+        }
+    }
+
+    /// Returns the width of a pointer on this target, in bits.
+    fn pointer_width(&self) -> u32 {
+        match self.target.arch {
+            Arch::X86
+            | Arch::Wasm32
+            | Arch::Mips
+            | Arch::PowerPc
+            | Arch::Sparc
+            | Arch::M68k
+            | Arch::RiscV32 => 32,
+            Arch::X86_64
+            | Arch::Wasm64
+            | Arch::Aarch64
+            | Arch::RiscV
+            | Arch::Z390
+            | Arch::SpirV
+            | Arch::RiscV64 => 64,
+            Arch::RiscV128 => 128,
+        }
+    }
+
+    /// Returns the byte order of this target, either `"little"` or `"big"`.
+    fn endian(&self) -> &'static str {
+        match self.target.arch {
+            Arch::Mips | Arch::PowerPc | Arch::Sparc | Arch::Z390 | Arch::M68k => "big",
+            Arch::X86
+            | Arch::X86_64
+            | Arch::Wasm32
+            | Arch::Wasm64
+            | Arch::Aarch64
+            | Arch::RiscV
+            | Arch::SpirV
+            | Arch::RiscV32
+            | Arch::RiscV64
+            | Arch::RiscV128 => "little",
+        }
+    }
+}
+
+fn arch_name(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86 => "x86",
+        Arch::X86_64 => "x86_64",
+        Arch::Wasm32 => "wasm32",
+        Arch::Wasm64 => "wasm64",
+        Arch::Aarch64 => "aarch64",
+        Arch::RiscV => "riscv",
+        Arch::Mips => "mips",
+        Arch::PowerPc => "powerpc",
+        Arch::Sparc => "sparc",
+        Arch::Z390 => "z390",
+        Arch::M68k => "m68k",
+        Arch::SpirV => "spirv",
+        Arch::RiscV32 => "riscv32",
+        Arch::RiscV64 => "riscv64",
+        Arch::RiscV128 => "riscv128",
+    }
+}
+
+/// The names other tools give an architecture.
+#[derive(Debug, Clone, Copy)]
+struct ArchAliases {
+    /// The GNU name, as used by `uname -m`, CMake and toolchain prefixes.
+    gnu: &'static str,
+    /// The CPU family in Meson's reference table.
+    meson: &'static str,
+    /// The suffix of its QEMU user-mode emulator, `qemu-<suffix>`.
+    qemu: Option<&'static str>,
+    /// The `target_arch` rustc reports.
+    rustc: &'static str,
+}
+
+/// Returns the names other tools use for `arch`, or `None` for
+/// architectures they do not know.
+fn arch_aliases(arch: Arch) -> Option<ArchAliases> {
+    let (gnu, meson, qemu, rustc) = match arch {
+        Arch::X86 => ("i686", "x86", Some("i386"), "x86"),
+        Arch::X86_64 => ("x86_64", "x86_64", Some("x86_64"), "x86_64"),
+        Arch::Wasm32 => ("wasm32", "wasm32", None, "wasm32"),
+        Arch::Wasm64 => ("wasm64", "wasm64", None, "wasm64"),
+        Arch::Aarch64 => ("aarch64", "aarch64", Some("aarch64"), "aarch64"),
+        Arch::Mips => ("mips", "mips", Some("mips"), "mips"),
+        Arch::PowerPc => ("powerpc", "ppc", Some("ppc"), "powerpc"),
+        Arch::Sparc => ("sparc", "sparc", Some("sparc"), "sparc"),
+        Arch::Z390 => ("s390x", "s390x", Some("s390x"), "s390x"),
+        Arch::M68k => ("m68k", "m68k", Some("m68k"), "m68k"),
+        Arch::RiscV32 => ("riscv32", "riscv32", Some("riscv32"), "riscv32"),
+        // `riscv` is the 64-bit variant
+        Arch::RiscV | Arch::RiscV64 => ("riscv64", "riscv64", Some("riscv64"), "riscv64"),
+        Arch::SpirV | Arch::RiscV128 => return None,
+    };
+    Some(ArchAliases {
+        gnu,
+        meson,
+        qemu,
+        rustc,
+    })
+}
+
+impl std::fmt::Display for TargetTriplet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}",
+            arch_name(self.target.arch),
+            self.vendor,
+            self.system
+        )
+    }
 }
 
 impl std::str::FromStr for TargetTriplet {
@@ -33,7 +183,7 @@ impl std::str::FromStr for TargetTriplet {
         }
 
         let host = parts[0].to_string();
-        let _vendor = parts[1].to_string();
+        let vendor = parts[1].to_string();
         let system = parts[2].to_string();
 
         let arch = match host.as_str() {
@@ -57,8 +207,10 @@ impl std::str::FromStr for TargetTriplet {
 
         let os = match system.as_str() {
             "windows" => Some(Os::Windows),
-            "linux" => Some(Os::Linux),
+            "linux" | "android" => Some(Os::Linux),
             "bsd" => Some(Os::Bsd),
+            // hf_codegen has no Darwin ABI or Mach-O output yet
+            "macos" | "ios" => return Err(ParseError::UnsupportedTargetTripletSystem),
             "solaris" => Some(Os::Solaris),
             "illumos" => Some(Os::Illumos),
             "haiku" => Some(Os::Haiku),
@@ -70,59 +222,477 @@ impl std::str::FromStr for TargetTriplet {
         let calling_convention = if let Some(os) = os {
             CallingConvention::from_arch_os(arch, os)
         } else {
-            // TODO: Match system to calling convention
-            todo!()
+            return Err(ParseError::UnknownTargetTripletSystem);
         };
 
         let target = Target::new(arch, calling_convention);
 
-        Ok(TargetTriplet { target })
+        Ok(TargetTriplet {
+            target,
+            vendor,
+            system,
+        })
     }
 }
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true
+)]
 struct Cli {
+    /// Prints compiler information and exits. `hf compile --print` takes the
+    /// same requests and answers them for that compilation.
+    #[arg(long, value_enum)]
+    print: Option<print::PrintRequest>,
+
+    /// Sets the target triplet used by `--print`. Defaults to the native target.
+    #[arg(long, requires = "print", value_parser = clap::value_parser!(TargetTriplet))]
+    target: Option<TargetTriplet>,
+
     #[command(subcommand)]
-    command: Command,
+    command: Option<Command>,
+}
+
+#[derive(Args, Debug, Default)]
+struct CompileArgs {
+    /// Sets the optimization level. 0 = no optimization, 3 = maximum optimization.
+    /// Defaults to 0.
+    #[arg(short, long)]
+    opt: Option<u8>,
+
+    /// Sets the target triplet. Can also be used to specify a target configuration file.
+    #[arg(long, value_parser = clap::value_parser!(TargetTriplet))]
+    target: Option<TargetTriplet>,
+
+    /// Sets the format of reported errors. Detected from the CI environment if not given.
+    #[arg(long, value_enum)]
+    error_format: Option<error_format::ErrorFormat>,
+
+    /// Adds all errors to the GitLab Code Quality report at this path, keeping
+    /// the issues already in it. Defaults to `gl-code-quality-report.json`
+    /// when running under GitLab CI.
+    #[arg(long)]
+    gitlab_codequality_report: Option<PathBuf>,
+
+    /// Treats source files removed while the build runs as skipped instead of failed.
+    #[arg(long)]
+    allow_vanishing_sources: bool,
+
+    /// Stops after writing object files. This is currently the only
+    /// behaviour, but scripts should pass it if they rely on it.
+    #[arg(long)]
+    no_link: bool,
+
+    /// Prints information about this compilation instead of compiling.
+    #[arg(long, value_enum)]
+    print: Option<print::PrintRequest>,
+
+    /// A list of files to compile.
+    #[arg(required_unless_present = "print")]
+    files: Vec<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
-    Compile {
-        /// Sets the optimization level. 0 = no optimization, 3 = maximum optimization.
-        #[arg(short, long, default_value_t = 0)]
-        opt: u8,
+    Compile(CompileArgs),
+    /// Accepts a subset of `rustc` flags and compiles with their hf equivalents.
+    Rustc {
+        /// The arguments that would be passed to `rustc`.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Runs Cargo with hf as its compiler.
+    ///
+    /// Every compiler invocation Cargo makes goes through `hf rustc`, which
+    /// answers Cargo's `rustc -vV` and `--print` probes, with these
+    /// limitations:
+    ///
+    /// - Each crate's sources are compiled as HF, so Rust packages fail to
+    ///   build at their first crate.
+    /// - The reported release is hf's own version, so packages that declare
+    ///   a `rust-version` are rejected by Cargo.
+    /// - Only the `rustc` flags understood by `hf rustc` have an effect, the
+    ///   rest are ignored with a warning.
+    /// - Only object files are emitted; Cargo steps that expect rlibs,
+    ///   metadata or linked binaries will not find them.
+    /// - A `RUSTC_WRAPPER` or `RUSTC` already set, e.g. for sccache, is
+    ///   replaced with a warning.
+    Cargo {
+        /// The Cargo subcommand and its arguments, e.g. `build --release`.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        subcommand: Vec<String>,
+    },
+    /// Shows whether and why each path matches a file pattern.
+    Match {
+        /// The pattern to test, with the same syntax used everywhere files are selected.
+        pattern: String,
+
+        /// Paths relative to the source root to test against the pattern.
+        paths: Vec<String>,
 
-        /// Sets the target triplet. Can also be used to specify a target configuration file.
+        /// Matches case insensitively.
+        #[arg(long)]
+        ignore_case: bool,
+    },
+    /// Writes a CMake toolchain file that uses hf as the compiler.
+    Cmake {
+        /// The path of the toolchain file to write.
+        #[arg(short, long, default_value = "hf-toolchain.cmake")]
+        output: PathBuf,
+
+        /// Sets the target triplet to cross-compile for. Defaults to the host.
         #[arg(long, value_parser = clap::value_parser!(TargetTriplet))]
         target: Option<TargetTriplet>,
+    },
+    /// Inspects the targets hf can compile for.
+    Targets {
+        #[command(subcommand)]
+        command: targets::TargetsCommand,
+    },
+    /// Writes a Meson cross file that uses hf as the compiler.
+    Meson {
+        /// The path of the cross file to write.
+        #[arg(short, long, default_value = "hf-cross.ini")]
+        output: PathBuf,
 
-        /// A list of files to compile.
-        #[arg(required = true)]
-        files: Vec<PathBuf>,
+        /// Sets the target triplet to cross-compile for. Defaults to the host.
+        #[arg(long, value_parser = clap::value_parser!(TargetTriplet))]
+        target: Option<TargetTriplet>,
     },
+    /// Checks this machine for problems that keep hf from working.
+    Diagnostics,
 }
 
 fn main() {
-    let cli = Cli::parse();
-
-    match cli.command {
-        Command::Compile { opt, target, files } => {
-            let target = target.map(|t| t.target).unwrap_or_else(|| Target::native());
-            if opt > 3 {
-                eprintln!("error: invalid optimization level, must be between 0 and 3");
-                std::process::exit(1);
+    let mut stdout = std::io::stdout();
+    let mut stderr = std::io::stderr();
+    let code = run(
+        std::env::args_os(),
+        &|name| std::env::var(name).ok(),
+        &mut StreamPolicy::new(&mut stdout, &mut stderr),
+    );
+    std::process::exit(code);
+}
+
+/// Runs the CLI with the given arguments, writing all output through `streams`.
+/// Environment variables are read through `env`.
+///
+/// Returns the exit code of the process.
+fn run<I, T>(args: I, env: &dyn Fn(&str) -> Option<String>, streams: &mut StreamPolicy) -> i32
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let mut args = match args::expand_response_files(args) {
+        Ok(args) => args,
+        Err(e) => {
+            writeln!(streams.human(), "error: {}", e).ok();
+            return 1;
+        }
+    };
+    args::append_encoded_rustflags(&mut args, env("CARGO_ENCODED_RUSTFLAGS").as_deref());
+
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            // Help and version output is the payload of those invocations
+            if e.use_stderr() {
+                write!(streams.human(), "{}", e.render()).ok();
+            } else {
+                streams.claim_stdout();
+                write!(streams.payload(), "{}", e.render()).ok();
+            }
+            return e.exit_code();
+        }
+    };
+
+    if let Some(request) = cli.print {
+        let args = CompileArgs {
+            target: cli.target,
+            ..Default::default()
+        };
+        streams.claim_stdout();
+        print::print(request, &args, env, streams);
+        return 0;
+    }
+
+    let Some(command) = cli.command else {
+        write!(streams.human(), "{}", Cli::command().render_help()).ok();
+        return 2;
+    };
+
+    match command {
+        Command::Compile(args) => {
+            if let Some(request) = args.print {
+                streams.claim_stdout();
+                print::print(request, &args, env, streams);
+                return 0;
+            }
+            let (options, _) = CompileOptions::resolve(&args, env);
+            compile_files(args.files, options, streams)
+        }
+        Command::Rustc { args } => {
+            let invocation = match rustc::translate(&args) {
+                Ok(invocation) => invocation,
+                Err(e) => {
+                    writeln!(streams.human(), "error: {}", e).ok();
+                    return 1;
+                }
+            };
+            let mut query_warnings = Vec::new();
+            if let Some(answer) = rustc::answer_queries(&invocation, &mut query_warnings) {
+                for warning in &query_warnings {
+                    writeln!(streams.human(), "warning: {}", warning).ok();
+                }
+                streams.claim_stdout();
+                write!(streams.payload(), "{}", answer).ok();
+                return 0;
+            }
+            for warning in &invocation.warnings {
+                writeln!(streams.human(), "warning: {}", warning).ok();
             }
-            let settings = CompilerSettings {
-                optimization_level: opt,
-                ..Default::default()
+            if invocation.files.is_empty() {
+                writeln!(streams.human(), "error: no input files").ok();
+                return 1;
+            }
+            let options = CompileOptions {
+                opt: invocation.opt,
+                target: invocation.target,
+                allow_vanishing_sources: false,
+                no_link: false,
+                error_format: error_format::ErrorFormat::detect(env),
+                // Cargo runs one of these per crate, which would pile up in
+                // whatever report a GitLab job had set up for other steps
+                gitlab_codequality_report: None,
             };
-            for file in files {
-                compile::compile(file, target.clone(), &settings).unwrap();
+            compile_files(invocation.files, options, streams)
+        }
+        Command::Cargo { subcommand } => cargo::cargo(&subcommand, streams),
+        Command::Match {
+            pattern,
+            paths,
+            ignore_case,
+        } => path_matcher::match_paths(&pattern, &paths, ignore_case, streams),
+        Command::Cmake { output, target } => cmake::cmake(output, target, streams),
+        Command::Meson { output, target } => meson::meson(output, target, streams),
+        Command::Targets { command } => targets::targets(command, streams),
+        Command::Diagnostics => diagnostics::diagnostics(streams),
+    }
+}
+
+/// The settings shared by every file of one compilation.
+struct CompileOptions {
+    opt: u8,
+    target: Option<TargetTriplet>,
+    allow_vanishing_sources: bool,
+    /// Compilation never links yet, so this only records the intent.
+    no_link: bool,
+    error_format: error_format::ErrorFormat,
+    gitlab_codequality_report: Option<PathBuf>,
+}
+
+/// Where the value of a setting came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SettingSource {
+    Cli,
+    Env,
+    Default,
+}
+
+impl SettingSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            SettingSource::Cli => "cli",
+            SettingSource::Env => "env",
+            SettingSource::Default => "default",
+        }
+    }
+}
+
+/// Where each of the [`CompileOptions`] came from.
+#[derive(Debug)]
+struct CompileSources {
+    opt: SettingSource,
+    target: SettingSource,
+    allow_vanishing_sources: SettingSource,
+    no_link: SettingSource,
+    error_format: SettingSource,
+    gitlab_codequality_report: SettingSource,
+}
+
+impl CompileOptions {
+    /// Takes each setting from the command line, then the CI environment,
+    /// then its default.
+    fn resolve(args: &CompileArgs, env: &dyn Fn(&str) -> Option<String>) -> (Self, CompileSources) {
+        let given = |given: bool| {
+            if given {
+                SettingSource::Cli
+            } else {
+                SettingSource::Default
             }
+        };
+        let detected_format = error_format::ErrorFormat::detect(env);
+        let detected_report = error_format::detect_gitlab_codequality_report(env);
+
+        let sources = CompileSources {
+            opt: given(args.opt.is_some()),
+            target: given(args.target.is_some()),
+            allow_vanishing_sources: given(args.allow_vanishing_sources),
+            no_link: given(args.no_link),
+            error_format: match args.error_format {
+                Some(_) => SettingSource::Cli,
+                None if detected_format != error_format::ErrorFormat::Human => SettingSource::Env,
+                None => SettingSource::Default,
+            },
+            gitlab_codequality_report: match &args.gitlab_codequality_report {
+                Some(_) => SettingSource::Cli,
+                None if detected_report.is_some() => SettingSource::Env,
+                None => SettingSource::Default,
+            },
+        };
+        let options = CompileOptions {
+            opt: args.opt.unwrap_or(0),
+            target: args.target.clone(),
+            allow_vanishing_sources: args.allow_vanishing_sources,
+            no_link: args.no_link,
+            error_format: args.error_format.unwrap_or(detected_format),
+            gitlab_codequality_report: args.gitlab_codequality_report.clone().or(detected_report),
+        };
+        (options, sources)
+    }
+}
+
+fn compile_files(files: Vec<PathBuf>, options: CompileOptions, streams: &mut StreamPolicy) -> i32 {
+    let sources = files
+        .into_iter()
+        .map(compile::SourceFile::discover)
+        .collect::<Vec<_>>();
+    compile_sources(sources, options, streams)
+}
+
+fn compile_sources(
+    sources: Vec<compile::SourceFile>,
+    options: CompileOptions,
+    streams: &mut StreamPolicy,
+) -> i32 {
+    let target = options
+        .target
+        .map(|t| t.target)
+        .unwrap_or_else(Target::native);
+    if options.opt > 3 {
+        writeln!(
+            streams.human(),
+            "error: invalid optimization level, must be between 0 and 3"
+        )
+        .ok();
+        return 1;
+    }
+    let settings = CompilerSettings {
+        optimization_level: options.opt,
+        ..Default::default()
+    };
+    if options.error_format.is_machine_readable() {
+        streams.claim_stdout();
+    }
+
+    let total = sources.len();
+    let mut failed = 0;
+    let mut vanished = 0;
+    let mut codequality_issues = Vec::new();
+    for source in sources {
+        let path = source.path.clone();
+        let result = compile::compile(
+            source,
+            target.clone(),
+            &settings,
+            options.error_format,
+            streams,
+        );
+        let Err(e) = result else {
+            continue;
+        };
+
+        if options.gitlab_codequality_report.is_some() {
+            codequality_issues.push(error_format::gitlab_issue(&e.diagnostic(&path)));
+        }
+        match e {
+            compile::CompilationError::SourceVanished => vanished += 1,
+            _ => failed += 1,
         }
     }
+
+    if let Some(report) = &options.gitlab_codequality_report {
+        // Earlier steps of the same CI job may have reported into this file
+        let existing = match std::fs::read_to_string(report) {
+            Ok(existing) => Some(existing),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                writeln!(
+                    streams.human(),
+                    "error: could not read {}: {}",
+                    report.display(),
+                    e
+                )
+                .ok();
+                return 1;
+            }
+        };
+        let contents = match error_format::gitlab_report(existing.as_deref(), &codequality_issues) {
+            Ok(contents) => contents,
+            Err(e) => {
+                writeln!(
+                    streams.human(),
+                    "error: could not add to {}: {}",
+                    report.display(),
+                    e
+                )
+                .ok();
+                return 1;
+            }
+        };
+        if let Err(e) = std::fs::write(report, contents) {
+            writeln!(
+                streams.human(),
+                "error: could not write {}: {}",
+                report.display(),
+                e
+            )
+            .ok();
+            return 1;
+        }
+    }
+
+    if total > 1 || failed > 0 || vanished > 0 {
+        writeln!(
+            streams.human(),
+            "Compiled {} of {} files ({} failed, {} vanished)",
+            total - failed - vanished,
+            total,
+            failed,
+            vanished
+        )
+        .ok();
+    }
+    if vanished > 0 && !options.allow_vanishing_sources {
+        writeln!(
+            streams.human(),
+            "error: {} source file(s) were removed during the build, \
+             pass --allow-vanishing-sources to ignore them",
+            vanished
+        )
+        .ok();
+        return 1;
+    }
+
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
 }
 
 #[cfg(test)]
@@ -152,4 +722,208 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_target_triplet_host_round_trip() {
+        let host = TargetTriplet::host().to_string();
+        match TargetTriplet::from_str(&host) {
+            Ok(parsed) => assert_eq!(parsed.to_string(), host),
+            // Darwin hosts are described but cannot be targeted yet
+            Err(e) => assert!(
+                matches!(e, ParseError::UnsupportedTargetTripletSystem),
+                "{}: {}",
+                host,
+                e
+            ),
+        }
+        assert!(matches!(
+            TargetTriplet::from_str("aarch64-apple-macos"),
+            Err(ParseError::UnsupportedTargetTripletSystem)
+        ));
+    }
+
+    #[test]
+    fn test_target_triplet_display() {
+        let triplets = vec![
+            "x86_64-unknown-linux",
+            "x86_64-pc-windows",
+            "x86-unknown-linux",
+        ];
+
+        for triplet in triplets {
+            let target_triplet = TargetTriplet::from_str(triplet).unwrap();
+            assert_eq!(target_triplet.to_string(), triplet);
+        }
+    }
+
+    #[test]
+    fn test_target_triplet_properties() {
+        let x86_64 = TargetTriplet::from_str("x86_64-unknown-linux").unwrap();
+        assert_eq!(x86_64.pointer_width(), 64);
+        assert_eq!(x86_64.endian(), "little");
+
+        let x86 = TargetTriplet::from_str("x86-unknown-windows").unwrap();
+        assert_eq!(x86.pointer_width(), 32);
+        assert_eq!(x86.endian(), "little");
+    }
+
+    /// Runs the CLI in an empty environment, so CI detection does not change
+    /// where output goes.
+    fn run_captured(args: &[&str]) -> (i32, String, String) {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let code = run(
+            args.iter().copied(),
+            &|_| None,
+            &mut StreamPolicy::new(&mut stdout, &mut stderr),
+        );
+        (
+            code,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_stream_policy() {
+        // Payload-producing modes write to stdout only
+        let (code, stdout, stderr) = run_captured(&["hf", "--print", "host"]);
+        assert_eq!(code, 0);
+        assert_eq!(stdout, format!("{}\n", TargetTriplet::host()));
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (code, stdout, stderr) = run_captured(&[
+            "hf",
+            "--print",
+            "pointer-width",
+            "--target",
+            "x86-unknown-linux",
+        ]);
+        assert_eq!(code, 0);
+        assert_eq!(stdout, "32\n");
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (code, stdout, stderr) = run_captured(&["hf", "--print", "endian"]);
+        assert_eq!(code, 0);
+        assert_eq!(stdout, format!("{}\n", TargetTriplet::host().endian()));
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (code, stdout, stderr) = run_captured(&["hf", "compile", "--print", "full-config"]);
+        assert_eq!(code, 0);
+        assert!(stdout.starts_with('{'), "unexpected stdout: {}", stdout);
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        // Both `--print` flags take every request
+        let (code, stdout, stderr) = run_captured(&[
+            "hf",
+            "--print",
+            "full-config",
+            "--target",
+            "x86-unknown-linux",
+        ]);
+        assert_eq!(code, 0);
+        assert!(
+            stdout.contains("\"target\":{\"value\":\"x86-unknown-linux\",\"source\":\"cli\"}"),
+            "unexpected stdout: {}",
+            stdout
+        );
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (code, stdout, stderr) = run_captured(&["hf", "compile", "--print", "host"]);
+        assert_eq!(code, 0);
+        assert_eq!(stdout, format!("{}\n", TargetTriplet::host()));
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (code, stdout, stderr) = run_captured(&["hf", "match", "*.hf", "main.hf"]);
+        assert_eq!(code, 0);
+        assert!(stdout.starts_with("main.hf: matched"));
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (_, stdout, stderr) = run_captured(&[
+            "hf",
+            "targets",
+            "check",
+            "not-a-triplet",
+            "--format",
+            "json",
+        ]);
+        assert!(
+            stdout.starts_with("{\"triplet\":"),
+            "unexpected stdout: {}",
+            stdout
+        );
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (code, stdout, stderr) = run_captured(&["hf", "--version"]);
+        assert_eq!(code, 0);
+        assert!(!stdout.is_empty());
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        let (code, stdout, stderr) = run_captured(&["hf", "rustc", "-vV"]);
+        assert_eq!(code, 0);
+        assert!(stdout.contains("\nhost: "), "unexpected stdout: {}", stdout);
+        assert!(stderr.is_empty(), "unexpected stderr: {}", stderr);
+
+        // Errors and diagnostics write to stderr only
+        let (code, stdout, stderr) = run_captured(&["hf", "--no-such-flag"]);
+        assert_ne!(code, 0);
+        assert!(stdout.is_empty(), "unexpected stdout: {}", stdout);
+        assert!(!stderr.is_empty());
+
+        let (code, stdout, stderr) = run_captured(&["hf", "compile", "--opt", "4", "a.hf"]);
+        assert_eq!(code, 1);
+        assert!(stdout.is_empty(), "unexpected stdout: {}", stdout);
+        assert!(!stderr.is_empty());
+
+        let (code, stdout, stderr) = run_captured(&["hf", "compile", "does-not-exist.hf"]);
+        assert_eq!(code, 1);
+        assert!(stdout.is_empty(), "unexpected stdout: {}", stdout);
+        assert!(stderr.contains("IO error"));
+
+        // Machine-readable diagnostics are the payload, the summary is not
+        let (code, stdout, stderr) = run_captured(&[
+            "hf",
+            "compile",
+            "--error-format",
+            "github",
+            "does-not-exist.hf",
+        ]);
+        assert_eq!(code, 1);
+        assert!(stdout.starts_with("::error file=does-not-exist.hf::"));
+        assert!(!stderr.contains("::error"));
+    }
+
+    #[test]
+    fn test_vanished_sources() {
+        let path = std::env::temp_dir().join("hf-cli-test-vanished.hf");
+
+        for allow_vanishing_sources in [false, true] {
+            std::fs::write(&path, "").unwrap();
+            let sources = vec![compile::SourceFile::discover(path.clone())];
+            std::fs::remove_file(&path).unwrap();
+
+            let mut stdout = Vec::new();
+            let mut stderr = Vec::new();
+            let options = CompileOptions {
+                opt: 0,
+                target: None,
+                allow_vanishing_sources,
+                no_link: false,
+                error_format: error_format::ErrorFormat::Human,
+                gitlab_codequality_report: None,
+            };
+            let code = compile_sources(
+                sources,
+                options,
+                &mut StreamPolicy::new(&mut stdout, &mut stderr),
+            );
+            let stderr = String::from_utf8(stderr).unwrap();
+
+            assert_eq!(code, if allow_vanishing_sources { 0 } else { 1 });
+            assert!(stdout.is_empty());
+            assert!(stderr.contains("was removed during the build"));
+            assert!(stderr.contains("(0 failed, 1 vanished)"));
+            assert!(!stderr.contains("IO error"));
+        }
+    }
 }
@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use crate::{process, stream::StreamPolicy, TargetTriplet};
+
+/// Writes a Meson cross file that uses hf as the compiler.
+pub fn meson(output: PathBuf, target: Option<TargetTriplet>, streams: &mut StreamPolicy) -> i32 {
+    let Some(hf) = process::hf_binary(streams) else {
+        return 1;
+    };
+
+    let target = target.unwrap_or_else(TargetTriplet::host);
+    let Some(contents) = cross_file(&hf, &target) else {
+        writeln!(
+            streams.human(),
+            "error: Meson has no CPU family for {}",
+            target
+        )
+        .ok();
+        return 1;
+    };
+    if let Err(e) = std::fs::write(&output, contents) {
+        writeln!(
+            streams.human(),
+            "error: could not write {}: {}",
+            output.display(),
+            e
+        )
+        .ok();
+        return 1;
+    }
+
+    writeln!(
+        streams.human(),
+        "Wrote Meson cross file {}!",
+        output.display()
+    )
+    .ok();
+    0
+}
+
+/// Returns `None` for architectures missing from Meson's CPU family table.
+fn cross_file(hf: &Path, target: &TargetTriplet) -> Option<String> {
+    let aliases = crate::arch_aliases(target.target.arch)?;
+    Some(format!(
+        "# Generated by `hf meson`.\n\
+         [binaries]\n\
+         c = '{hf}'\n\
+         \n\
+         [host_machine]\n\
+         system = '{system}'\n\
+         cpu_family = '{cpu_family}'\n\
+         cpu = '{cpu}'\n\
+         endian = '{endian}'\n",
+        hf = meson_string(&hf.to_string_lossy().replace('\\', "/")),
+        system = system(&target.system),
+        cpu_family = aliases.meson,
+        cpu = aliases.gnu,
+        endian = target.endian(),
+    ))
+}
+
+/// Escapes `s` for a single-quoted Meson string.
+fn meson_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn system(system: &str) -> &str {
+    match system {
+        "bsd" => "freebsd",
+        "macos" | "ios" => "darwin",
+        "solaris" | "illumos" => "sunos",
+        system => system,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_cross_file() {
+        let target = TargetTriplet::from_str("x86_64-unknown-linux").unwrap();
+        let contents = cross_file(Path::new("/opt/hf/bin/hf"), &target).unwrap();
+
+        assert!(contents.contains("[binaries]\nc = '/opt/hf/bin/hf'\n"));
+        assert!(contents.contains("system = 'linux'\n"));
+        assert!(contents.contains("cpu_family = 'x86_64'\n"));
+        assert!(contents.contains("endian = 'little'\n"));
+
+        let target = TargetTriplet::from_str("x86-unknown-linux").unwrap();
+        let contents = cross_file(Path::new("/home/o'neil/hf"), &target).unwrap();
+        assert!(contents.contains("c = '/home/o\\'neil/hf'\n"));
+        assert!(contents.contains("cpu_family = 'x86'\n"));
+        assert!(contents.contains("cpu = 'i686'\n"));
+
+        let target = TargetTriplet::from_str("spirv-unknown-linux").unwrap();
+        assert!(cross_file(Path::new("/opt/hf/bin/hf"), &target).is_none());
+
+        // Darwin only reaches this through the host triplet
+        assert_eq!(system("macos"), "darwin");
+    }
+}
@@ -0,0 +1,350 @@
+use crate::stream::StreamPolicy;
+
+/// Matches paths against an ordered list of glob patterns.
+///
+/// Every place that selects files by pattern goes through this type so all
+/// of them share the same semantics:
+///
+/// - Patterns are anchored at the source root: `*.hf` matches `main.hf` but
+///   not `src/main.hf`. Paths are given relative to the same root, and `\`
+///   is treated as a separator so Windows paths match the same way.
+/// - Within a segment `*` matches any run of characters and `?` matches a
+///   single character. A segment that is exactly `**` matches zero or more
+///   whole segments.
+/// - A pattern starting with `!` is a negation. Patterns are checked in
+///   order and the last one that matches decides, so `!` patterns re-exclude
+///   paths included by earlier ones.
+/// - Matching is case sensitive unless the matcher is created with
+///   `case_sensitive` set to `false`.
+#[derive(Debug, Clone)]
+pub struct PathMatcher {
+    patterns: Vec<Pattern>,
+    case_sensitive: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    source: String,
+    negated: bool,
+    components: Vec<String>,
+}
+
+/// Why a path was (or was not) selected by a [`PathMatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// The pattern that decided the outcome, as written.
+    pub pattern: String,
+    /// Whether the path is selected, i.e. the deciding pattern is not a negation.
+    pub included: bool,
+    /// Each pattern component paired with the path segments it matched.
+    pub trace: Vec<(String, String)>,
+}
+
+impl PathMatcher {
+    pub fn new<S: AsRef<str>>(patterns: &[S], case_sensitive: bool) -> Self {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                let source = pattern.as_ref().to_string();
+                let (negated, glob) = match source.strip_prefix('!') {
+                    Some(glob) => (true, glob),
+                    None => (false, source.as_str()),
+                };
+                let components = split_path(glob).map(str::to_string).collect();
+                Pattern {
+                    negated,
+                    components,
+                    source,
+                }
+            })
+            .collect();
+
+        PathMatcher {
+            patterns,
+            case_sensitive,
+        }
+    }
+
+    /// Returns whether `path` is selected by this matcher.
+    #[cfg(test)]
+    pub fn is_match(&self, path: &str) -> bool {
+        self.explain(path).is_some_and(|m| m.included)
+    }
+
+    /// Returns the pattern that decides whether `path` is selected, or `None`
+    /// if no pattern matches it.
+    pub fn explain(&self, path: &str) -> Option<Match> {
+        let segments = split_path(path).collect::<Vec<_>>();
+
+        self.patterns.iter().rev().find_map(|pattern| {
+            let mut trace = Vec::new();
+            match_components(
+                &pattern.components,
+                &segments,
+                self.case_sensitive,
+                &mut trace,
+            )
+            .then(|| Match {
+                pattern: pattern.source.clone(),
+                included: !pattern.negated,
+                trace,
+            })
+        })
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\'])
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+}
+
+fn match_components(
+    components: &[String],
+    segments: &[&str],
+    case_sensitive: bool,
+    trace: &mut Vec<(String, String)>,
+) -> bool {
+    let Some((component, rest)) = components.split_first() else {
+        return segments.is_empty();
+    };
+
+    if component == "**" {
+        for taken in 0..=segments.len() {
+            trace.push((component.clone(), segments[..taken].join("/")));
+            if match_components(rest, &segments[taken..], case_sensitive, trace) {
+                return true;
+            }
+            trace.pop();
+        }
+        return false;
+    }
+
+    let Some((segment, remaining)) = segments.split_first() else {
+        return false;
+    };
+    if !match_segment(component, segment, case_sensitive) {
+        return false;
+    }
+
+    trace.push((component.clone(), segment.to_string()));
+    if match_components(rest, remaining, case_sensitive, trace) {
+        return true;
+    }
+    trace.pop();
+    false
+}
+
+fn match_segment(glob: &str, segment: &str, case_sensitive: bool) -> bool {
+    let (glob, segment) = if case_sensitive {
+        (
+            glob.chars().collect::<Vec<_>>(),
+            segment.chars().collect::<Vec<_>>(),
+        )
+    } else {
+        (
+            glob.to_lowercase().chars().collect::<Vec<_>>(),
+            segment.to_lowercase().chars().collect::<Vec<_>>(),
+        )
+    };
+
+    // Greedy wildcard matching, backtracking to the most recent `*`
+    let (mut g, mut s) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while s < segment.len() {
+        if g < glob.len() && (glob[g] == '?' || glob[g] == segment[s]) {
+            g += 1;
+            s += 1;
+        } else if g < glob.len() && glob[g] == '*' {
+            star = Some((g, s));
+            g += 1;
+        } else if let Some((star_g, star_s)) = star {
+            g = star_g + 1;
+            s = star_s + 1;
+            star = Some((star_g, star_s + 1));
+        } else {
+            return false;
+        }
+    }
+
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+/// Prints whether and why each path matches `pattern`.
+///
+/// Returns `0` if at least one path matched, `1` otherwise.
+pub fn match_paths(
+    pattern: &str,
+    paths: &[String],
+    ignore_case: bool,
+    streams: &mut StreamPolicy,
+) -> i32 {
+    let matcher = PathMatcher::new(&[pattern], !ignore_case);
+    streams.claim_stdout();
+    let out = streams.payload();
+
+    let mut any_matched = false;
+    for path in paths {
+        match matcher.explain(path) {
+            Some(m) => {
+                any_matched |= m.included;
+                let verdict = if m.included { "matched" } else { "excluded" };
+                writeln!(out, "{}: {} by `{}`", path, verdict, m.pattern).ok();
+                for (component, segments) in m.trace {
+                    writeln!(out, "    `{}` matched `{}`", component, segments).ok();
+                }
+            }
+            None => {
+                writeln!(out, "{}: no match", path).ok();
+            }
+        }
+    }
+
+    if any_matched {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_matcher() {
+        let cases = vec![
+            ("*.hf", "main.hf", true),
+            ("*.hf", "src/main.hf", false),
+            ("src/*.hf", "src/main.hf", true),
+            ("src/**/*.hf", "src/main.hf", true),
+            ("src/**/*.hf", "src/a/b/main.hf", true),
+            ("src/**", "src", true),
+            ("**/fixtures/**", "tests/fixtures/a.hf", true),
+            ("ma?n.hf", "main.hf", true),
+            ("ma?n.hf", "maiin.hf", false),
+            ("*.HF", "main.hf", false),
+            ("src/*", "src\\main.hf", true),
+            ("./src/*.hf", "src/main.hf", true),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYbZ", false),
+        ];
+
+        for (pattern, path, expected) in cases {
+            let matcher = PathMatcher::new(&[pattern], true);
+            assert_eq!(
+                matcher.is_match(path),
+                expected,
+                "pattern `{}` against `{}`",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_path_matcher_case_insensitive() {
+        let matcher = PathMatcher::new(&["SRC/*.HF"], false);
+        assert!(matcher.is_match("src/main.hf"));
+        assert!(matcher.is_match("Src/Main.Hf"));
+    }
+
+    #[test]
+    fn test_path_matcher_negation() {
+        let matcher = PathMatcher::new(&["**/*.hf", "!tests/**", "tests/keep.hf"], true);
+        assert!(matcher.is_match("src/main.hf"));
+        assert!(!matcher.is_match("tests/fixtures/a.hf"));
+        assert!(matcher.is_match("tests/keep.hf"));
+        assert!(!matcher.is_match("README.md"));
+
+        let m = matcher.explain("tests/a.hf").unwrap();
+        assert_eq!(m.pattern, "!tests/**");
+        assert!(!m.included);
+    }
+
+    /// Generates pseudo-random relative paths without wildcard characters.
+    fn random_paths(count: usize) -> Vec<String> {
+        const ALPHABET: &[u8] = b"abcXYZ019.-_ ";
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = move |bound: usize| {
+            // xorshift64, so failures reproduce without a dependency
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+
+        (0..count)
+            .map(|_| {
+                (0..1 + next(4))
+                    .map(|_| {
+                        let segment = (0..1 + next(6))
+                            .map(|_| ALPHABET[next(ALPHABET.len())] as char)
+                            .collect::<String>();
+                        // `.` segments are dropped when splitting
+                        if segment == "." {
+                            "a.".to_string()
+                        } else {
+                            segment
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_path_matcher_properties() {
+        for path in random_paths(500) {
+            let segments = path.split('/').collect::<Vec<_>>();
+            let matches = |pattern: &str| PathMatcher::new(&[pattern], true).is_match(&path);
+
+            // A path used as its own pattern always matches, with either separator
+            assert!(matches(&path), "`{}` against itself", path);
+            assert!(PathMatcher::new(&[path.as_str()], true).is_match(&path.replace('/', "\\")));
+            // `**` matches everything
+            assert!(matches("**"));
+            // Patterns are anchored, an extra leading segment never matches
+            assert!(!matches(&format!("Q/{}", path)), "`Q/{}`", path);
+            // Negating the last pattern always excludes
+            let negated = format!("!{}", path);
+            assert!(!PathMatcher::new(&["**", negated.as_str()], true).is_match(&path));
+            // Case insensitive matching accepts anything case sensitive matching does
+            assert!(PathMatcher::new(&[path.to_uppercase()], false).is_match(&path));
+
+            for i in 0..segments.len() {
+                // Generalising one segment to `*`, or one character to `?`, still matches
+                let mut generalised = segments.clone();
+                generalised[i] = "*";
+                assert!(matches(&generalised.join("/")), "`{:?}`", generalised);
+
+                let mut chars = segments[i].chars().collect::<Vec<_>>();
+                chars[0] = '?';
+                let mut generalised = segments.clone();
+                let segment = chars.into_iter().collect::<String>();
+                generalised[i] = &segment;
+                assert!(matches(&generalised.join("/")), "`{:?}`", generalised);
+
+                // Replacing any run of segments with `**` still matches
+                for j in i..=segments.len() {
+                    let pattern = [&segments[..i], &["**"], &segments[j..]].concat().join("/");
+                    assert!(matches(&pattern), "`{}` against `{}`", pattern, path);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_path_matcher_trace() {
+        let matcher = PathMatcher::new(&["src/**/*.hf"], true);
+        let m = matcher.explain("src/a/b/main.hf").unwrap();
+        assert_eq!(
+            m.trace,
+            vec![
+                ("src".to_string(), "src".to_string()),
+                ("**".to_string(), "a/b".to_string()),
+                ("*.hf".to_string(), "main.hf".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use clap::ValueEnum;
+
+use crate::{
+    json, stream::StreamPolicy, CompileArgs, CompileOptions, CompileSources, TargetTriplet,
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PrintRequest {
+    /// The target triplet of the machine running the compiler.
+    Host,
+    /// The pointer width of the selected target, in bits.
+    PointerWidth,
+    /// The byte order of the selected target, `little` or `big`.
+    Endian,
+    /// The settings `hf compile` would use as JSON, with where each one came from.
+    FullConfig,
+}
+
+/// Answers `request` for a compilation with `args`.
+///
+/// The top-level `--print` passes the defaults with only `--target` set.
+pub(crate) fn print(
+    request: PrintRequest,
+    args: &CompileArgs,
+    env: &dyn Fn(&str) -> Option<String>,
+    streams: &mut StreamPolicy,
+) {
+    let target = || args.target.clone().unwrap_or_else(TargetTriplet::host);
+    let out = streams.payload();
+    match request {
+        PrintRequest::Host => writeln!(out, "{}", TargetTriplet::host()),
+        PrintRequest::PointerWidth => writeln!(out, "{}", target().pointer_width()),
+        PrintRequest::Endian => writeln!(out, "{}", target().endian()),
+        PrintRequest::FullConfig => {
+            let (options, sources) = CompileOptions::resolve(args, env);
+            writeln!(out, "{}", full_config(&options, &sources))
+        }
+    }
+    .ok();
+}
+
+/// Formats the effective compile settings, each as `{"value":..,"source":..}`
+/// where the source is `cli`, `env` or `default`.
+pub(crate) fn full_config(options: &CompileOptions, sources: &CompileSources) -> String {
+    // Compilation falls back to the native target, which is what host() names
+    let target = options.target.clone().unwrap_or_else(TargetTriplet::host);
+    let error_format = options
+        .error_format
+        .to_possible_value()
+        .expect("no error format is hidden");
+    let report = options
+        .gitlab_codequality_report
+        .as_ref()
+        .map(|path| path.to_string_lossy().to_string());
+
+    let settings = [
+        ("target", json::string(&target.to_string()), sources.target),
+        ("optimization_level", options.opt.to_string(), sources.opt),
+        (
+            "error_format",
+            json::string(error_format.get_name()),
+            sources.error_format,
+        ),
+        (
+            "gitlab_codequality_report",
+            json::optional_string(report.as_deref()),
+            sources.gitlab_codequality_report,
+        ),
+        (
+            "allow_vanishing_sources",
+            options.allow_vanishing_sources.to_string(),
+            sources.allow_vanishing_sources,
+        ),
+        ("no_link", options.no_link.to_string(), sources.no_link),
+    ];
+    let settings = settings
+        .iter()
+        .map(|(name, value, source)| {
+            format!(
+                "{}:{{\"value\":{},\"source\":{}}}",
+                json::string(name),
+                value,
+                json::string(source.as_str())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{{{}}}", settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, str::FromStr};
+
+    #[test]
+    fn test_full_config() {
+        let args = CompileArgs {
+            opt: Some(2),
+            target: Some(TargetTriplet::from_str("x86_64-unknown-linux").unwrap()),
+            error_format: None,
+            gitlab_codequality_report: None,
+            allow_vanishing_sources: false,
+            no_link: true,
+            print: Some(PrintRequest::FullConfig),
+            files: Vec::new(),
+        };
+        let ci = |name: &str| match name {
+            "GITHUB_ACTIONS" => Some("true".to_string()),
+            "GITLAB_CI" => Some("true".to_string()),
+            _ => None,
+        };
+        let (options, sources) = CompileOptions::resolve(&args, &ci);
+        assert_eq!(
+            full_config(&options, &sources),
+            "{\"target\":{\"value\":\"x86_64-unknown-linux\",\"source\":\"cli\"},\
+             \"optimization_level\":{\"value\":2,\"source\":\"cli\"},\
+             \"error_format\":{\"value\":\"github\",\"source\":\"env\"},\
+             \"gitlab_codequality_report\":{\"value\":\"gl-code-quality-report.json\",\"source\":\"env\"},\
+             \"allow_vanishing_sources\":{\"value\":false,\"source\":\"default\"},\
+             \"no_link\":{\"value\":true,\"source\":\"cli\"}}"
+        );
+
+        let args = CompileArgs {
+            opt: None,
+            target: None,
+            gitlab_codequality_report: Some(PathBuf::from("report.json")),
+            no_link: false,
+            ..args
+        };
+        let (options, sources) = CompileOptions::resolve(&args, &|_| None);
+        let defaults = full_config(&options, &sources);
+        assert!(defaults.contains("\"optimization_level\":{\"value\":0,\"source\":\"default\"}"));
+        assert!(defaults.contains("\"error_format\":{\"value\":\"human\",\"source\":\"default\"}"));
+        assert!(defaults.contains(
+            "\"gitlab_codequality_report\":{\"value\":\"report.json\",\"source\":\"cli\"}"
+        ));
+    }
+}
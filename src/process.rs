@@ -0,0 +1,248 @@
+use std::{
+    ffi::OsStr,
+    path::PathBuf,
+    process::{Command, ExitStatus},
+};
+
+use crate::stream::StreamPolicy;
+
+/// Returns the path of the running hf binary, for tools that should call
+/// back into it. Reports the error and returns `None` if it is unknown.
+pub fn hf_binary(streams: &mut StreamPolicy) -> Option<PathBuf> {
+    match std::env::current_exe() {
+        Ok(hf) => Some(hf),
+        Err(e) => {
+            writeln!(
+                streams.human(),
+                "error: could not locate the hf binary: {}",
+                e
+            )
+            .ok();
+            None
+        }
+    }
+}
+
+/// Searches `PATH` for an executable called `name`.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    find_program_in(&path, name, std::env::consts::EXE_SUFFIX)
+}
+
+fn find_program_in(path: &OsStr, name: &str, exe_suffix: &str) -> Option<PathBuf> {
+    std::env::split_paths(path).find_map(|dir| {
+        let candidate = dir.join(name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+
+        // Appended rather than set, so `ld.lld` finds `ld.lld.exe`, not `ld.exe`
+        let candidate = dir.join(format!("{}{}", name, exe_suffix));
+        (!exe_suffix.is_empty() && candidate.is_file()).then_some(candidate)
+    })
+}
+
+/// How a child process ended.
+#[derive(Debug)]
+pub enum ExitOutcome {
+    /// The process exited on its own with the given code.
+    Exited(i32),
+    /// The process was terminated by a signal.
+    #[cfg(unix)]
+    Signaled {
+        signal: i32,
+        name: Option<&'static str>,
+    },
+    /// The process crashed with an NTSTATUS exception code.
+    #[cfg(windows)]
+    Crashed { code: u32, name: &'static str },
+    /// The process could not be started at all.
+    SpawnFailed(std::io::Error),
+}
+
+impl ExitOutcome {
+    /// Runs `command` to completion and classifies how it ended.
+    pub fn wait(command: &mut Command) -> Self {
+        match command.status() {
+            Ok(status) => Self::from_status(status),
+            Err(e) => Self::SpawnFailed(e),
+        }
+    }
+
+    pub fn from_status(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+
+            if let Some(signal) = status.signal() {
+                return Self::Signaled {
+                    signal,
+                    name: signal_name(signal),
+                };
+            }
+        }
+
+        #[cfg(windows)]
+        if let Some(code) = status.code() {
+            if let Some(name) = ntstatus_name(code as u32) {
+                return Self::Crashed {
+                    code: code as u32,
+                    name,
+                };
+            }
+        }
+
+        Self::Exited(status.code().unwrap_or(1))
+    }
+
+    /// Returns the exit code to propagate, following shell conventions.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Exited(code) => *code,
+            #[cfg(unix)]
+            Self::Signaled { signal, .. } => 128 + signal,
+            #[cfg(windows)]
+            Self::Crashed { code, .. } => *code as i32,
+            Self::SpawnFailed(e) if e.kind() == std::io::ErrorKind::NotFound => 127,
+            Self::SpawnFailed(_) => 126,
+        }
+    }
+
+    /// Describes the outcome for a diagnostic, e.g.
+    /// `linker terminated by SIGKILL (likely out of memory)`.
+    pub fn describe(&self, program: &str) -> String {
+        match self {
+            Self::Exited(code) => format!("{} exited with code {}", program, code),
+            #[cfg(unix)]
+            Self::Signaled { signal, name } => {
+                let name = name
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("signal {}", signal));
+                let hint = match name.as_str() {
+                    "SIGKILL" => " (likely out of memory)",
+                    "SIGSEGV" => " (segmentation fault)",
+                    _ => "",
+                };
+                format!("{} terminated by {}{}", program, name, hint)
+            }
+            #[cfg(windows)]
+            Self::Crashed { code, name } => {
+                format!("{} crashed with {} ({:#010x})", program, name, code)
+            }
+            Self::SpawnFailed(e) => format!("failed to run {}: {}", program, e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn signal_name(signal: i32) -> Option<&'static str> {
+    // Only signals whose numbers are the same on every unix
+    match signal {
+        1 => Some("SIGHUP"),
+        2 => Some("SIGINT"),
+        3 => Some("SIGQUIT"),
+        4 => Some("SIGILL"),
+        6 => Some("SIGABRT"),
+        8 => Some("SIGFPE"),
+        9 => Some("SIGKILL"),
+        11 => Some("SIGSEGV"),
+        13 => Some("SIGPIPE"),
+        14 => Some("SIGALRM"),
+        15 => Some("SIGTERM"),
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+fn ntstatus_name(code: u32) -> Option<&'static str> {
+    match code {
+        0xC0000005 => Some("STATUS_ACCESS_VIOLATION"),
+        0xC000001D => Some("STATUS_ILLEGAL_INSTRUCTION"),
+        0xC0000094 => Some("STATUS_INTEGER_DIVIDE_BY_ZERO"),
+        0xC00000FD => Some("STATUS_STACK_OVERFLOW"),
+        0xC000013A => Some("STATUS_CONTROL_C_EXIT"),
+        0xC0000374 => Some("STATUS_HEAP_CORRUPTION"),
+        0xC0000409 => Some("STATUS_STACK_BUFFER_OVERRUN"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_program_exe_suffix() {
+        let dir = std::env::temp_dir().join("hf-cli-test-find-program");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ld.exe"), "").unwrap();
+        let path = dir.clone().into_os_string();
+
+        assert_eq!(find_program_in(&path, "ld.lld", ".exe"), None);
+        std::fs::write(dir.join("ld.lld.exe"), "").unwrap();
+        assert_eq!(
+            find_program_in(&path, "ld.lld", ".exe"),
+            Some(dir.join("ld.lld.exe"))
+        );
+        assert_eq!(
+            find_program_in(&path, "ld", ".exe"),
+            Some(dir.join("ld.exe"))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spawn_failed() {
+        let outcome = ExitOutcome::wait(&mut Command::new("hf-cli-test-does-not-exist"));
+        assert!(matches!(outcome, ExitOutcome::SpawnFailed(_)));
+        assert_eq!(outcome.exit_code(), 127);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_outcome_unix() {
+        let sh = |script: &str| ExitOutcome::wait(Command::new("sh").args(["-c", script]));
+
+        let outcome = sh("exit 3");
+        assert!(matches!(outcome, ExitOutcome::Exited(3)));
+        assert_eq!(outcome.exit_code(), 3);
+        assert_eq!(outcome.describe("linker"), "linker exited with code 3");
+
+        let outcome = sh("kill -SEGV $$");
+        assert!(matches!(outcome, ExitOutcome::Signaled { signal: 11, .. }));
+        assert_eq!(outcome.exit_code(), 139);
+        assert_eq!(
+            outcome.describe("linker"),
+            "linker terminated by SIGSEGV (segmentation fault)"
+        );
+
+        let outcome = sh("kill -KILL $$");
+        assert_eq!(outcome.exit_code(), 137);
+        assert_eq!(
+            outcome.describe("linker"),
+            "linker terminated by SIGKILL (likely out of memory)"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_exit_outcome_windows() {
+        let outcome = ExitOutcome::wait(Command::new("cmd").args(["/C", "exit 3"]));
+        assert!(matches!(outcome, ExitOutcome::Exited(3)));
+
+        // cmd reports the exit code it is given as the process status
+        let outcome = ExitOutcome::wait(Command::new("cmd").args(["/C", "exit -1073741819"]));
+        assert!(matches!(
+            outcome,
+            ExitOutcome::Crashed {
+                code: 0xC0000005,
+                ..
+            }
+        ));
+        assert!(outcome
+            .describe("linker")
+            .contains("STATUS_ACCESS_VIOLATION"));
+    }
+}
@@ -0,0 +1,508 @@
+use std::{path::PathBuf, str::FromStr};
+use thiserror::Error;
+
+use crate::TargetTriplet;
+
+/// `rustc` flags that take a value but have no hf equivalent.
+const IGNORED_VALUE_FLAGS: &[&str] = &[
+    "--cfg",
+    "--check-cfg",
+    "--extern",
+    "--out-dir",
+    "--cap-lints",
+    "--error-format",
+    "--json",
+    "-o",
+    "-L",
+    "-l",
+    "-W",
+    "-A",
+    "-D",
+    "-F",
+    "-Z",
+];
+
+#[derive(Debug, Error)]
+pub enum RustcError {
+    #[error("missing value for `{0}`")]
+    MissingValue(String),
+
+    #[error("invalid value `{1}` for `{0}`")]
+    InvalidValue(String, String),
+}
+
+/// A `rustc` command line translated to its hf equivalent.
+#[derive(Debug)]
+pub struct RustcInvocation {
+    pub opt: u8,
+    pub target: Option<TargetTriplet>,
+    pub files: Vec<PathBuf>,
+    /// Set by `-V`, with `verbose` set by `-v` as well for Cargo's `-vV`.
+    pub version: bool,
+    pub verbose: bool,
+    /// The `--print` requests, in the order they were given.
+    pub prints: Vec<String>,
+    /// Only used to answer `--print file-names`.
+    pub crate_name: Option<String>,
+    pub crate_types: Vec<String>,
+    /// Flags that were accepted but have no effect.
+    pub warnings: Vec<String>,
+}
+
+/// Translates `rustc` arguments into an hf compilation.
+///
+/// Unsupported flags are ignored and reported in
+/// [`RustcInvocation::warnings`] rather than rejected, so build systems that
+/// pass extra flags keep working.
+pub fn translate(args: &[String]) -> Result<RustcInvocation, RustcError> {
+    let mut invocation = RustcInvocation {
+        opt: 0,
+        target: None,
+        files: Vec::new(),
+        version: false,
+        verbose: false,
+        prints: Vec::new(),
+        crate_name: None,
+        crate_types: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = split_flag(arg);
+        match flag {
+            "-C" | "--codegen" => {
+                let option = take_value(flag, inline_value, &mut args)?;
+                translate_codegen_option(&option, &mut invocation)?;
+            }
+            "-O" => invocation.opt = 2,
+            "-V" | "--version" => invocation.version = true,
+            "-v" | "--verbose" => {
+                invocation.verbose = true;
+                invocation.version |= inline_value == Some("V");
+            }
+            "--print" => invocation
+                .prints
+                .push(take_value(flag, inline_value, &mut args)?),
+            "--crate-name" => {
+                invocation.crate_name = Some(take_value(flag, inline_value, &mut args)?);
+            }
+            "--target" => {
+                let value = take_value(flag, inline_value, &mut args)?;
+                invocation.target = translate_target(&value);
+                if invocation.target.is_none() {
+                    invocation.warnings.push(format!(
+                        "`--target {}` has no hf equivalent, compiling for the native target",
+                        value
+                    ));
+                }
+            }
+            "--edition" => {
+                // HF has no editions, every edition compiles the same way
+                take_value(flag, inline_value, &mut args)?;
+            }
+            "--emit" => {
+                let kinds = take_value(flag, inline_value, &mut args)?;
+                for kind in kinds.split(',') {
+                    let kind = kind.split('=').next().unwrap_or(kind);
+                    if kind != "obj" {
+                        invocation.warnings.push(format!(
+                            "`--emit {}` is not supported, only object files are emitted",
+                            kind
+                        ));
+                    }
+                }
+            }
+            "--crate-type" => {
+                let crate_type = take_value(flag, inline_value, &mut args)?;
+                if crate_type != "lib" {
+                    invocation.warnings.push(format!(
+                        "`--crate-type {}` is not supported, emitting an object file",
+                        crate_type
+                    ));
+                }
+                invocation.crate_types.push(crate_type);
+            }
+            flag if IGNORED_VALUE_FLAGS.contains(&flag) => {
+                take_value(flag, inline_value, &mut args)?;
+                invocation
+                    .warnings
+                    .push(format!("ignoring unsupported flag `{}`", flag));
+            }
+            flag if flag.starts_with('-') => {
+                invocation
+                    .warnings
+                    .push(format!("ignoring unsupported flag `{}`", flag));
+            }
+            _ => invocation.files.push(PathBuf::from(arg)),
+        }
+    }
+
+    Ok(invocation)
+}
+
+/// Answers the queries Cargo makes before it builds anything: `-vV` and the
+/// `--print` requests of its target probe.
+///
+/// Returns `None` when the invocation compiles instead. Print requests hf
+/// cannot answer are left out of the answer and reported in `warnings`.
+pub fn answer_queries(invocation: &RustcInvocation, warnings: &mut Vec<String>) -> Option<String> {
+    if invocation.version {
+        return Some(version(invocation.verbose));
+    }
+    if invocation.prints.is_empty() {
+        return None;
+    }
+
+    let target = invocation
+        .target
+        .clone()
+        .unwrap_or_else(TargetTriplet::host);
+    // rustc names crates read from stdin `rust_out`
+    let crate_name = invocation.crate_name.as_deref().unwrap_or("rust_out");
+    let mut lines = Vec::new();
+    for request in &invocation.prints {
+        match request.as_str() {
+            "file-names" => {
+                let crate_types = match invocation.crate_types.as_slice() {
+                    [] => &["bin".to_string()][..],
+                    crate_types => crate_types,
+                };
+                for crate_type in crate_types {
+                    match file_name(crate_type, crate_name, &target) {
+                        Some(name) => lines.push(name),
+                        None => warnings.push(format!("unknown crate type `{}`", crate_type)),
+                    }
+                }
+            }
+            "sysroot" => lines.push(sysroot()),
+            "split-debuginfo" => lines.push("off".to_string()),
+            "crate-name" => lines.push(crate_name.to_string()),
+            "cfg" => lines.extend(cfg(&target)),
+            _ => warnings.push(format!(
+                "`--print {}` is not supported, printing nothing for it",
+                request
+            )),
+        }
+    }
+
+    Some(lines.iter().map(|line| format!("{}\n", line)).collect())
+}
+
+/// Formats `rustc -V`, or `rustc -vV` when `verbose`.
+///
+/// Cargo reads the `host:` and `release:` lines. The release is hf's own
+/// version, so Cargo rejects packages that declare a `rust-version`.
+fn version(verbose: bool) -> String {
+    let release = env!("CARGO_PKG_VERSION");
+    let mut version = format!("hf-rustc {}\n", release);
+    if verbose {
+        version.push_str(&format!(
+            "binary: hf-rustc\nhost: {}\nrelease: {}\n",
+            rustc_host(),
+            release
+        ));
+    }
+    version
+}
+
+/// Returns the rustc triple of the machine running hf, e.g.
+/// `x86_64-unknown-linux-gnu`.
+fn rustc_host() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86" => "i686",
+        "riscv64" => "riscv64gc",
+        arch => arch,
+    };
+    let rest = match std::env::consts::OS {
+        "linux" if cfg!(target_env = "musl") => "unknown-linux-musl".to_string(),
+        "linux" => "unknown-linux-gnu".to_string(),
+        "macos" => "apple-darwin".to_string(),
+        "windows" if cfg!(target_env = "gnu") => "pc-windows-gnu".to_string(),
+        "windows" => "pc-windows-msvc".to_string(),
+        os => format!("unknown-{}", os),
+    };
+    format!("{}-{}", arch, rest)
+}
+
+/// Returns the file rustc would write for a crate of `crate_type`.
+fn file_name(crate_type: &str, crate_name: &str, target: &TargetTriplet) -> Option<String> {
+    let windows = target.system == "windows";
+    let dylib_suffix = match target.system.as_str() {
+        "windows" => "dll",
+        "macos" | "ios" => "dylib",
+        _ => "so",
+    };
+    let name = match crate_type {
+        "bin" if windows => format!("{}.exe", crate_name),
+        "bin" => crate_name.to_string(),
+        "lib" | "rlib" => format!("lib{}.rlib", crate_name),
+        "staticlib" if windows => format!("{}.lib", crate_name),
+        "staticlib" => format!("lib{}.a", crate_name),
+        "dylib" | "cdylib" | "proc-macro" if windows => format!("{}.{}", crate_name, dylib_suffix),
+        "dylib" | "cdylib" | "proc-macro" => format!("lib{}.{}", crate_name, dylib_suffix),
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The install prefix, the directory above the one holding hf.
+fn sysroot() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent()?.parent().map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .display()
+        .to_string()
+}
+
+/// Returns the `--print cfg` lines describing `target`.
+fn cfg(target: &TargetTriplet) -> Vec<String> {
+    let arch = target.target.arch;
+    let arch = crate::arch_aliases(arch).map_or(crate::arch_name(arch), |aliases| aliases.rustc);
+    let os = match target.system.as_str() {
+        "bsd" => "freebsd",
+        os => os,
+    };
+    let family = if os == "windows" { "windows" } else { "unix" };
+
+    vec![
+        family.to_string(),
+        format!("target_arch=\"{}\"", arch),
+        format!("target_endian=\"{}\"", target.endian()),
+        format!("target_family=\"{}\"", family),
+        format!("target_os=\"{}\"", os),
+        format!("target_pointer_width=\"{}\"", target.pointer_width()),
+        format!("target_vendor=\"{}\"", target.vendor),
+    ]
+}
+
+/// Translates a rustc target triple, e.g. `x86_64-unknown-linux-gnu`, into
+/// the hf triplet for the same machine.
+///
+/// hf triplets are accepted as they are. Otherwise the environment part is
+/// dropped and the names rustc uses are mapped to hf's, returning `None` if
+/// the result does not describe a target hf supports.
+fn translate_target(triple: &str) -> Option<TargetTriplet> {
+    if let Ok(target) = TargetTriplet::from_str(triple) {
+        return Some(target);
+    }
+
+    let mut parts = triple.split('-');
+    let arch = match parts.next()? {
+        "i386" | "i586" | "i686" => "x86",
+        "riscv64gc" => "riscv64",
+        "riscv32i" | "riscv32imac" | "riscv32imc" => "riscv32",
+        arch => arch,
+    };
+    let vendor = parts.next()?;
+    let system = match parts.next()? {
+        "darwin" => "macos",
+        system => system,
+    };
+    TargetTriplet::from_str(&format!("{}-{}-{}", arch, vendor, system)).ok()
+}
+
+/// Splits `--flag=value` and `-Xvalue` into the flag and its inline value.
+fn split_flag(arg: &str) -> (&str, Option<&str>) {
+    if arg.starts_with("--") {
+        match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value)),
+            None => (arg, None),
+        }
+    } else if arg.starts_with('-') && arg.len() > 2 && arg.is_char_boundary(2) {
+        (&arg[..2], Some(&arg[2..]))
+    } else {
+        (arg, None)
+    }
+}
+
+fn take_value(
+    flag: &str,
+    inline_value: Option<&str>,
+    args: &mut std::slice::Iter<'_, String>,
+) -> Result<String, RustcError> {
+    match inline_value {
+        Some(value) => Ok(value.to_string()),
+        None => args
+            .next()
+            .cloned()
+            .ok_or_else(|| RustcError::MissingValue(flag.to_string())),
+    }
+}
+
+fn translate_codegen_option(
+    option: &str,
+    invocation: &mut RustcInvocation,
+) -> Result<(), RustcError> {
+    let (name, value) = option.split_once('=').unwrap_or((option, ""));
+    match name {
+        "opt-level" => {
+            invocation.opt = match value {
+                "0" => 0,
+                "1" => 1,
+                "2" => 2,
+                "3" => 3,
+                "s" | "z" => {
+                    invocation.warnings.push(format!(
+                        "`-C opt-level={}` is not supported, using opt-level 2",
+                        value
+                    ));
+                    2
+                }
+                _ => {
+                    return Err(RustcError::InvalidValue(
+                        "-C opt-level".to_string(),
+                        value.to_string(),
+                    ))
+                }
+            }
+        }
+        "target-cpu" => invocation.warnings.push(format!(
+            "`-C target-cpu={}` has no effect, hf does not tune for specific CPUs",
+            value
+        )),
+        _ => invocation
+            .warnings
+            .push(format!("ignoring unsupported codegen option `-C {}`", name)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_translate() {
+        let invocation = translate(&args(&[
+            "--edition=2021",
+            "--crate-type",
+            "lib",
+            "--emit=obj",
+            "-C",
+            "opt-level=3",
+            "-Ctarget-cpu=native",
+            "--target",
+            "x86_64-unknown-linux",
+            "src/main.hf",
+        ]))
+        .unwrap();
+
+        assert_eq!(invocation.opt, 3);
+        assert_eq!(
+            invocation.target.unwrap().to_string(),
+            "x86_64-unknown-linux"
+        );
+        assert_eq!(invocation.files, vec![PathBuf::from("src/main.hf")]);
+        assert_eq!(invocation.warnings.len(), 1);
+        assert!(invocation.warnings[0].contains("target-cpu"));
+    }
+
+    #[test]
+    fn test_translate_ignores_unsupported_flags() {
+        let invocation = translate(&args(&[
+            "--crate-name",
+            "foo",
+            "--crate-type=bin",
+            "-L",
+            "dependency=target/deps",
+            "--emit=dep-info,metadata,link",
+            "-Cdebuginfo=2",
+            "--verbose",
+            "main.hf",
+        ]))
+        .unwrap();
+
+        assert_eq!(invocation.opt, 0);
+        assert_eq!(invocation.files, vec![PathBuf::from("main.hf")]);
+        assert_eq!(invocation.warnings.len(), 6);
+    }
+
+    #[test]
+    fn test_answer_queries() {
+        let answer = |argv: &[&str]| {
+            let invocation = translate(&args(argv)).unwrap();
+            answer_queries(&invocation, &mut Vec::new())
+        };
+
+        let version = answer(&["-vV"]).unwrap();
+        assert!(version.starts_with("hf-rustc "));
+        assert!(version.contains(&format!("\nhost: {}\n", rustc_host())));
+        assert!(version.contains(&format!("\nrelease: {}\n", env!("CARGO_PKG_VERSION"))));
+
+        // The probe Cargo runs before every build
+        let probe = answer(&[
+            "-",
+            "--crate-name",
+            "___",
+            "--print=file-names",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "--crate-type",
+            "bin",
+            "--crate-type",
+            "rlib",
+            "--crate-type",
+            "staticlib",
+            "--print=sysroot",
+            "--print=split-debuginfo",
+            "--print=crate-name",
+            "--print=cfg",
+        ])
+        .unwrap();
+        let lines = probe.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..3], ["___", "lib___.rlib", "lib___.a"]);
+        assert_eq!(lines[4..6], ["off", "___"]);
+        assert!(lines.contains(&"target_arch=\"x86_64\""));
+        assert!(lines.contains(&"target_os=\"linux\""));
+        assert!(lines.contains(&"unix"));
+
+        assert!(answer(&["main.hf"]).is_none());
+    }
+
+    #[test]
+    fn test_translate_target() {
+        let target = |triple: &str| translate_target(triple).map(|t| t.to_string());
+
+        assert_eq!(
+            target("x86_64-unknown-linux").as_deref(),
+            Some("x86_64-unknown-linux")
+        );
+        assert_eq!(
+            target("x86_64-unknown-linux-gnu").as_deref(),
+            Some("x86_64-unknown-linux")
+        );
+        assert_eq!(
+            target("i686-pc-windows-msvc").as_deref(),
+            Some("x86-pc-windows")
+        );
+        assert_eq!(target("aarch64-apple-darwin"), None);
+        assert_eq!(target("thumbv7em-none-eabihf"), None);
+
+        let invocation =
+            translate(&args(&["--target", "aarch64-apple-darwin", "main.hf"])).unwrap();
+        assert!(invocation.target.is_none());
+        assert_eq!(
+            invocation.warnings,
+            vec!["`--target aarch64-apple-darwin` has no hf equivalent, compiling for the native target"]
+        );
+    }
+
+    #[test]
+    fn test_translate_errors() {
+        assert!(matches!(
+            translate(&args(&["-C", "opt-level=4"])),
+            Err(RustcError::InvalidValue(..))
+        ));
+        assert!(matches!(
+            translate(&args(&["--target"])),
+            Err(RustcError::MissingValue(..))
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::Write;
+
+/// Decides which stream each piece of output is written to.
+///
+/// Machine-consumable payloads (such as `--print` results) go to stdout,
+/// everything meant for humans (diagnostics, progress, debug dumps) goes to
+/// stderr. Print sites ask the policy for a writer instead of using
+/// `println!`/`eprintln!` directly, so pipelines only ever see payloads.
+pub struct StreamPolicy<'a> {
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+    stdout_claimed: bool,
+}
+
+impl<'a> StreamPolicy<'a> {
+    pub fn new(stdout: &'a mut dyn Write, stderr: &'a mut dyn Write) -> Self {
+        StreamPolicy {
+            stdout,
+            stderr,
+            stdout_claimed: false,
+        }
+    }
+
+    /// Claims stdout for the mode that produces this invocation's payload.
+    ///
+    /// Only one mode may own stdout per invocation; claiming it twice means
+    /// two writers would interleave their output.
+    pub fn claim_stdout(&mut self) {
+        debug_assert!(!self.stdout_claimed, "stdout was claimed twice");
+        self.stdout_claimed = true;
+    }
+
+    /// Returns the writer for machine-consumable output.
+    pub fn payload(&mut self) -> &mut (dyn Write + 'a) {
+        debug_assert!(
+            self.stdout_claimed,
+            "payload written to stdout without claiming it"
+        );
+        &mut *self.stdout
+    }
+
+    /// Returns the writer for human-facing output.
+    pub fn human(&mut self) -> &mut (dyn Write + 'a) {
+        &mut *self.stderr
+    }
+}
@@ -0,0 +1,540 @@
+use clap::{Subcommand, ValueEnum};
+use hf_codegen::{
+    compiler::{CompilerSettings, HfCompiler},
+    target::Arch,
+};
+use std::{panic::AssertUnwindSafe, path::PathBuf, str::FromStr};
+
+use crate::{json, process, stream::StreamPolicy, TargetTriplet};
+
+/// The program compiled to check that the backend can emit objects.
+///
+/// An empty program never reaches instruction selection, so this one needs
+/// a function with a body for the backend to lower.
+const SMOKE_PROGRAM: &str = "fn main() {\n    let answer = 40 + 2;\n}\n";
+
+#[derive(Subcommand, Debug)]
+pub enum TargetsCommand {
+    /// Checks whether this machine can produce artifacts for a target.
+    Check {
+        /// The target triplet to check.
+        triplet: String,
+
+        /// Also checks that a linker for the target is available.
+        #[arg(long)]
+        link: bool,
+
+        /// Also checks that binaries for the target can be run.
+        #[arg(long)]
+        run: bool,
+
+        /// Sets the output format of the results.
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Fail => "FAIL",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Step {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    hint: Option<String>,
+}
+
+/// Why a probe failed, with a hint on how to fix it.
+#[derive(Debug)]
+pub struct Failure {
+    pub detail: String,
+    pub hint: String,
+}
+
+type Probe = fn(&TargetTriplet, &dyn Environment) -> Result<String, Failure>;
+
+/// The parts of the machine the probes look at, so tests can inject faults.
+pub trait Environment {
+    /// Compiles the smoke program for `target`, returning the object size.
+    fn compile_smoke_program(&self, target: &TargetTriplet) -> Result<usize, String>;
+
+    fn find_program(&self, name: &str) -> Option<PathBuf>;
+}
+
+pub struct HostEnvironment;
+
+impl Environment for HostEnvironment {
+    fn compile_smoke_program(&self, target: &TargetTriplet) -> Result<usize, String> {
+        compile_smoke_program(target)
+    }
+
+    fn find_program(&self, name: &str) -> Option<PathBuf> {
+        process::find_program(name)
+    }
+}
+
+/// Compiles the smoke program for `target`, returning the object size.
+pub fn compile_smoke_program(target: &TargetTriplet) -> Result<usize, String> {
+    let tokens = hf_parser_rust::token::tokenize(SMOKE_PROGRAM)
+        .map_err(|e| format!("error during tokenization: {:?}", e))?;
+    let ast = hf_parser_rust::ast::build_ast(tokens)
+        .map_err(|e| format!("error while building ast: {:?}", e))?;
+    let ir = hf_codegen::ir::from_ast(ast);
+
+    // Unsupported targets may panic deep in the backend, which is exactly
+    // what the callers want to report
+    let target = target.target.clone();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(move || {
+        let mut compiler = HfCompiler::new(target, CompilerSettings::default());
+        compiler
+            .compile_to_object_file(ir, "smoke.hf")
+            .map(|obj| obj.write())
+    }));
+
+    match result {
+        Ok(Ok(Ok(raw))) => Ok(raw.len()),
+        Ok(Ok(Err(e))) => Err(format!("failed to write object file: {:?}", e)),
+        Ok(Err(e)) => Err(format!("compiler error: {:?}", e)),
+        Err(_) => Err("the backend panicked".to_string()),
+    }
+}
+
+pub fn targets(command: TargetsCommand, streams: &mut StreamPolicy) -> i32 {
+    match command {
+        TargetsCommand::Check {
+            triplet,
+            link,
+            run,
+            format,
+        } => {
+            let steps = check(&triplet, link, run, &HostEnvironment);
+
+            streams.claim_stdout();
+            let out = streams.payload();
+            let report = match format {
+                Format::Human => format_human(&steps),
+                Format::Json => format_json(&triplet, &steps),
+            };
+            write!(out, "{}", report).ok();
+
+            if steps.iter().any(|step| step.status == Status::Fail) {
+                1
+            } else {
+                0
+            }
+        }
+    }
+}
+
+/// Returns the linkers that can link objects for `target`, in order of
+/// preference, or nothing if there is no known linker for it.
+///
+/// The host's `cc` only links for the host, cross linkers are looked up by
+/// their GNU toolchain prefix, e.g. `aarch64-linux-gnu-gcc`.
+pub fn linker_candidates(target: &TargetTriplet) -> Vec<String> {
+    let arch = target.target.arch;
+    let candidates: &[&str] = if matches!(arch, Arch::Wasm32 | Arch::Wasm64) {
+        &["wasm-ld"]
+    } else if target.system == "windows" {
+        &["link.exe", "lld-link"]
+    } else if is_host(target) {
+        &["cc", "ld", "ld.lld"]
+    } else if target.system == "linux" {
+        let Some(aliases) = crate::arch_aliases(arch) else {
+            return Vec::new();
+        };
+        return vec![
+            format!("{}-linux-gnu-gcc", aliases.gnu),
+            format!("{}-linux-gnu-ld", aliases.gnu),
+        ];
+    } else {
+        &[]
+    };
+    candidates.iter().map(|name| name.to_string()).collect()
+}
+
+/// Runs the probes in order, skipping everything after the first failure.
+fn check(triplet: &str, link: bool, run: bool, env: &dyn Environment) -> Vec<Step> {
+    let mut probes = vec![("codegen", probe_codegen as Probe)];
+    if link {
+        probes.push(("link", probe_link));
+    }
+    if run {
+        probes.push(("run", probe_run));
+    }
+
+    let mut steps = Vec::new();
+    let target = match TargetTriplet::from_str(triplet) {
+        Ok(target) => {
+            steps.push(Step {
+                name: "parse",
+                status: Status::Pass,
+                detail: target.to_string(),
+                hint: None,
+            });
+            Some(target)
+        }
+        Err(e) => {
+            steps.push(Step {
+                name: "parse",
+                status: Status::Fail,
+                detail: e.to_string(),
+                hint: Some(
+                    "triplets have the form <arch>-<vendor>-<system>, e.g. x86_64-unknown-linux"
+                        .to_string(),
+                ),
+            });
+            None
+        }
+    };
+
+    let mut failed = target.is_none();
+    for (name, probe) in probes {
+        let step = match &target {
+            Some(target) if !failed => match probe(target, env) {
+                Ok(detail) => Step {
+                    name,
+                    status: Status::Pass,
+                    detail,
+                    hint: None,
+                },
+                Err(failure) => {
+                    failed = true;
+                    Step {
+                        name,
+                        status: Status::Fail,
+                        detail: failure.detail,
+                        hint: Some(failure.hint),
+                    }
+                }
+            },
+            _ => Step {
+                name,
+                status: Status::Skipped,
+                detail: "an earlier step failed".to_string(),
+                hint: None,
+            },
+        };
+        steps.push(step);
+    }
+
+    steps
+}
+
+fn probe_codegen(target: &TargetTriplet, env: &dyn Environment) -> Result<String, Failure> {
+    env.compile_smoke_program(target)
+        .map(|size| format!("emitted a {} byte object file", size))
+        .map_err(|detail| Failure {
+            detail,
+            hint: format!("the backend cannot generate code for {} yet", target),
+        })
+}
+
+pub fn probe_link(target: &TargetTriplet, env: &dyn Environment) -> Result<String, Failure> {
+    let candidates = linker_candidates(target);
+    if candidates.is_empty() {
+        return Err(Failure {
+            detail: format!("no known linker for {}", target),
+            hint: "link the objects on a machine of that target instead".to_string(),
+        });
+    }
+    match candidates.iter().find_map(|name| env.find_program(name)) {
+        Some(path) => Ok(format!("found {}", path.display())),
+        None => Err(Failure {
+            detail: format!("no linker found (looked for {})", candidates.join(", ")),
+            hint: format!(
+                "install a linker for {} and make sure it is on PATH",
+                target
+            ),
+        }),
+    }
+}
+
+/// Whether binaries for `target` are native to the machine running hf.
+fn is_host(target: &TargetTriplet) -> bool {
+    let host = TargetTriplet::host();
+    target.target.arch == host.target.arch && target.system == host.system
+}
+
+/// Returns the program that runs binaries for `target` on another machine,
+/// or `None` if there is no known one.
+fn runner(target: &TargetTriplet) -> Option<String> {
+    let arch = target.target.arch;
+    if matches!(arch, Arch::Wasm32 | Arch::Wasm64) {
+        return Some("wasmtime".to_string());
+    }
+    match target.system.as_str() {
+        "windows" if matches!(arch, Arch::X86 | Arch::X86_64) => Some("wine".to_string()),
+        // qemu-user only emulates Linux system calls
+        "linux" => crate::arch_aliases(arch)?
+            .qemu
+            .map(|qemu| format!("qemu-{}", qemu)),
+        _ => None,
+    }
+}
+
+fn probe_run(target: &TargetTriplet, env: &dyn Environment) -> Result<String, Failure> {
+    if is_host(target) {
+        return Ok("target is the host, binaries run natively".to_string());
+    }
+
+    let Some(runner) = runner(target) else {
+        return Err(Failure {
+            detail: format!("no known runner for {}", target),
+            hint: "run the binaries on a machine of that target instead".to_string(),
+        });
+    };
+    match env.find_program(&runner) {
+        Some(path) => Ok(format!("found runner {}", path.display())),
+        None => Err(Failure {
+            detail: format!("no runner found (looked for {})", runner),
+            hint: format!(
+                "install {} to run {} binaries on this machine",
+                runner, target
+            ),
+        }),
+    }
+}
+
+fn format_human(steps: &[Step]) -> String {
+    let mut out = String::new();
+    for step in steps {
+        out.push_str(&format!(
+            "{:<8} {:<7} {}\n",
+            step.name,
+            step.status.as_str(),
+            step.detail
+        ));
+        if let Some(hint) = &step.hint {
+            out.push_str(&format!("{:<8} {:<7} hint: {}\n", "", "", hint));
+        }
+    }
+    out
+}
+
+fn format_json(triplet: &str, steps: &[Step]) -> String {
+    let steps = steps
+        .iter()
+        .map(|step| {
+            format!(
+                "{{\"name\":{},\"status\":{},\"detail\":{},\"hint\":{}}}",
+                json::string(step.name),
+                json::string(&step.status.as_str().to_lowercase()),
+                json::string(&step.detail),
+                json::optional_string(step.hint.as_deref()),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "{{\"triplet\":{},\"steps\":[{}]}}\n",
+        json::string(triplet),
+        steps
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeEnvironment {
+        codegen: Result<usize, String>,
+        programs: Vec<&'static str>,
+    }
+
+    impl Environment for FakeEnvironment {
+        fn compile_smoke_program(&self, _target: &TargetTriplet) -> Result<usize, String> {
+            self.codegen.clone()
+        }
+
+        fn find_program(&self, name: &str) -> Option<PathBuf> {
+            self.programs
+                .contains(&name)
+                .then(|| PathBuf::from("/usr/bin").join(name))
+        }
+    }
+
+    fn working_environment() -> FakeEnvironment {
+        FakeEnvironment {
+            codegen: Ok(128),
+            programs: vec!["cc", "link.exe", "qemu-i386", "wine"],
+        }
+    }
+
+    /// A target that parses but is never the machine running the tests.
+    fn cross_triplet() -> &'static str {
+        if TargetTriplet::host().system == "windows" {
+            "x86-unknown-linux"
+        } else {
+            "x86-unknown-windows"
+        }
+    }
+
+    fn statuses(steps: &[Step]) -> Vec<(&'static str, Status)> {
+        steps.iter().map(|step| (step.name, step.status)).collect()
+    }
+
+    #[test]
+    fn test_check_passes() {
+        let host = TargetTriplet::host().to_string();
+        let steps = check(&host, true, true, &working_environment());
+        assert_eq!(
+            statuses(&steps),
+            vec![
+                ("parse", Status::Pass),
+                ("codegen", Status::Pass),
+                ("link", Status::Pass),
+                ("run", Status::Pass),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_probe_parse_failure() {
+        let steps = check("not-a-triplet", true, false, &working_environment());
+        assert_eq!(
+            statuses(&steps),
+            vec![
+                ("parse", Status::Fail),
+                ("codegen", Status::Skipped),
+                ("link", Status::Skipped),
+            ]
+        );
+        assert!(steps[0].hint.is_some());
+    }
+
+    #[test]
+    fn test_probe_codegen_failure() {
+        let env = FakeEnvironment {
+            codegen: Err("compiler error: Unsupported".to_string()),
+            ..working_environment()
+        };
+        let steps = check("x86_64-unknown-linux", true, true, &env);
+        assert_eq!(
+            statuses(&steps),
+            vec![
+                ("parse", Status::Pass),
+                ("codegen", Status::Fail),
+                ("link", Status::Skipped),
+                ("run", Status::Skipped),
+            ]
+        );
+        assert_eq!(steps[1].detail, "compiler error: Unsupported");
+    }
+
+    #[test]
+    fn test_probe_link_failure() {
+        let env = FakeEnvironment {
+            programs: Vec::new(),
+            ..working_environment()
+        };
+        let host = TargetTriplet::host();
+        let steps = check(&host.to_string(), true, false, &env);
+        assert_eq!(steps[2].status, Status::Fail);
+        assert_eq!(
+            steps[2].detail,
+            format!(
+                "no linker found (looked for {})",
+                linker_candidates(&host).join(", ")
+            )
+        );
+        if host.system != "windows" {
+            assert_eq!(linker_candidates(&host), ["cc", "ld", "ld.lld"]);
+        }
+
+        // The host's cc cannot link for another machine
+        let steps = check("mips-unknown-bsd", true, false, &working_environment());
+        assert_eq!(steps[2].status, Status::Fail);
+        assert_eq!(steps[2].detail, "no known linker for mips-unknown-bsd");
+    }
+
+    #[test]
+    fn test_linker_candidates() {
+        let candidates =
+            |triplet: &str| linker_candidates(&TargetTriplet::from_str(triplet).unwrap());
+
+        assert_eq!(candidates("wasm32-unknown-linux"), ["wasm-ld"]);
+        assert_eq!(candidates("x86-unknown-windows"), ["link.exe", "lld-link"]);
+        assert_eq!(
+            candidates("z390-unknown-linux"),
+            ["s390x-linux-gnu-gcc", "s390x-linux-gnu-ld"]
+        );
+        assert!(candidates("spirv-unknown-linux").is_empty());
+    }
+
+    #[test]
+    fn test_probe_run() {
+        let runner = if TargetTriplet::host().system == "windows" {
+            "qemu-i386"
+        } else {
+            "wine"
+        };
+        let steps = check(cross_triplet(), false, true, &working_environment());
+        assert_eq!(steps[2].status, Status::Pass);
+        assert!(steps[2].detail.contains(runner), "{}", steps[2].detail);
+
+        let env = FakeEnvironment {
+            programs: Vec::new(),
+            ..working_environment()
+        };
+        let steps = check(cross_triplet(), false, true, &env);
+        assert_eq!(steps[2].status, Status::Fail);
+        assert_eq!(
+            steps[2].detail,
+            format!("no runner found (looked for {})", runner)
+        );
+    }
+
+    #[test]
+    fn test_runner() {
+        let runner = |triplet: &str| runner(&TargetTriplet::from_str(triplet).unwrap());
+
+        assert_eq!(runner("x86-unknown-windows").as_deref(), Some("wine"));
+        assert_eq!(runner("x86_64-unknown-windows").as_deref(), Some("wine"));
+        assert_eq!(runner("x86-unknown-linux").as_deref(), Some("qemu-i386"));
+        assert_eq!(runner("z390-unknown-linux").as_deref(), Some("qemu-s390x"));
+        assert_eq!(runner("wasm32-unknown-linux").as_deref(), Some("wasmtime"));
+        assert_eq!(runner("aarch64-unknown-windows"), None);
+        assert_eq!(runner("aarch64-unknown-bsd"), None);
+        assert_eq!(runner("spirv-unknown-linux"), None);
+        assert_eq!(runner("riscv128-unknown-linux"), None);
+    }
+
+    #[test]
+    fn test_format_json() {
+        let env = FakeEnvironment {
+            programs: Vec::new(),
+            ..working_environment()
+        };
+        let steps = check("x86_64-unknown-windows", true, false, &env);
+        assert_eq!(
+            format_json("x86_64-unknown-windows", &steps),
+            "{\"triplet\":\"x86_64-unknown-windows\",\"steps\":[\
+             {\"name\":\"parse\",\"status\":\"pass\",\"detail\":\"x86_64-unknown-windows\",\"hint\":null},\
+             {\"name\":\"codegen\",\"status\":\"pass\",\"detail\":\"emitted a 128 byte object file\",\"hint\":null},\
+             {\"name\":\"link\",\"status\":\"fail\",\"detail\":\"no linker found (looked for link.exe, lld-link)\",\
+             \"hint\":\"install a linker for x86_64-unknown-windows and make sure it is on PATH\"}]}\n"
+        );
+    }
+}